@@ -0,0 +1,452 @@
+use crate::{
+    color::ColorSupport,
+    error::{Error, Result},
+    style::Theme,
+    stylesheet::StylesheetFile,
+    terminal::TerminalGuard,
+    ui::{Backend, Block, Context, CrosstermBackend, ScreenMode, UI},
+};
+use crossterm::event::{self, Event};
+use std::{
+    cell::RefCell,
+    io::{self, stdout},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for input when there is nothing else to wake up for.
+const IDLE_POLL: Duration = Duration::from_secs(1);
+/// How often to look for changes to the stylesheet file.
+const STYLESHEET_POLL: Duration = Duration::from_millis(250);
+
+/// What `update` wants the app to do next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    Quit,
+}
+
+/// What `update` gets called with.
+#[derive(Debug)]
+pub enum AppEvent<M> {
+    /// Sent by a callback in the view.
+    Message(M),
+    /// Terminal input, after the view has had it.
+    Input(Event),
+    /// The tick timer went off.
+    Tick,
+    /// The stylesheet file changed but could not be read or parsed. The
+    /// previous stylesheet stays until the file is fixed.
+    StylesheetError(Error),
+}
+
+/// Where callbacks in the view send messages for `update`.
+pub struct Messages<M>(RefCell<Vec<M>>);
+
+impl<M> Messages<M> {
+    pub fn send(&self, message: M) {
+        self.0.borrow_mut().push(message);
+    }
+
+    fn take(&self) -> Vec<M> {
+        self.0.take()
+    }
+}
+
+impl<M> Default for Messages<M> {
+    fn default() -> Self {
+        Self(RefCell::new(vec![]))
+    }
+}
+
+/// Runs an app made of a state, a `view` that builds the element tree from
+/// it, and an `update` that changes it. The tree is rebuilt from the state
+/// after every update, so callbacks in it never hold on to the state; they
+/// send messages instead.
+pub struct App<S> {
+    state: S,
+    tick_rate: Option<Duration>,
+    frame_interval: Duration,
+    screen: ScreenMode,
+    keep_final_frame: Option<bool>,
+    theme: Option<Theme>,
+    stylesheet: Option<PathBuf>,
+    color_support: Option<ColorSupport>,
+}
+
+impl<S> App<S> {
+    pub fn new(state: S) -> Self {
+        Self {
+            state,
+            tick_rate: None,
+            frame_interval: Duration::from_secs(1) / 60,
+            screen: ScreenMode::Fullscreen,
+            keep_final_frame: None,
+            theme: None,
+            stylesheet: None,
+            color_support: None,
+        }
+    }
+
+    /// See `UI::screen`.
+    pub fn screen(mut self, screen: ScreenMode) -> Self {
+        self.screen = screen;
+        self
+    }
+
+    /// See `UI::keep_final_frame`.
+    pub fn keep_final_frame(mut self, keep: bool) -> Self {
+        self.keep_final_frame = Some(keep);
+        self
+    }
+
+    /// Draws the app in `theme` instead of the context's own.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Draws in `colors` instead of what the terminal was detected to
+    /// support.
+    pub fn color_support(mut self, colors: ColorSupport) -> Self {
+        self.color_support = Some(colors);
+        self
+    }
+
+    /// Styles the app with the stylesheet file at `path`, and again every
+    /// time the file changes. Changes that fail to load are sent to
+    /// `update` as `AppEvent::StylesheetError`.
+    pub fn stylesheet<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.stylesheet = Some(path.into());
+        self
+    }
+
+    fn ui<'v, B: Backend>(&self, backend: B, root: Block<'v>) -> UI<'v, B> {
+        let ui = UI::new(backend, root).screen(self.screen);
+        match self.keep_final_frame {
+            Some(keep) => ui.keep_final_frame(keep),
+            None => ui,
+        }
+    }
+
+    /// Sends `AppEvent::Tick` to `update` every `rate`.
+    pub fn tick_rate(mut self, rate: Duration) -> Self {
+        self.tick_rate = Some(rate);
+        self
+    }
+
+    /// Caps how many frames are drawn per second.
+    pub fn frame_rate(mut self, fps: u32) -> Self {
+        self.frame_interval = Duration::from_secs(1) / fps.max(1);
+        self
+    }
+
+    /// Takes over the terminal and runs the app until `update` returns
+    /// `Action::Quit`. Returns the final state. The terminal is restored
+    /// however the app ends, panics included.
+    pub fn run<M, V, U>(self, view: V, update: U) -> Result<S>
+    where
+        V: for<'v> FnMut(&'v S, &'v Messages<M>) -> Block<'v>,
+        U: FnMut(&mut S, AppEvent<M>) -> Action,
+    {
+        let ctx = Context::new()?;
+        let mut guard = TerminalGuard::new()?;
+
+        let result = self.run_with(
+            CrosstermBackend::new(stdout()),
+            ctx,
+            view,
+            update,
+            |timeout| {
+                if event::poll(timeout)? {
+                    event::read().map(Some)
+                } else {
+                    Ok(None)
+                }
+            },
+        );
+
+        guard.restore()?;
+        result
+    }
+
+    /// The loop behind `run`, on any backend and event source. `next_event`
+    /// waits up to the given time for input.
+    pub fn run_with<B, M, V, U, E>(
+        mut self,
+        mut backend: B,
+        mut ctx: Context,
+        mut view: V,
+        mut update: U,
+        mut next_event: E,
+    ) -> Result<S>
+    where
+        B: Backend,
+        V: for<'v> FnMut(&'v S, &'v Messages<M>) -> Block<'v>,
+        U: FnMut(&mut S, AppEvent<M>) -> Action,
+        E: FnMut(Duration) -> io::Result<Option<Event>>,
+    {
+        let messages = Messages::default();
+        let mut next_tick = self.tick_rate.map(|rate| Instant::now() + rate);
+        let mut next_frame = Instant::now();
+        if let Some(theme) = self.theme.take() {
+            ctx.theme = theme;
+        }
+        if let Some(colors) = self.color_support {
+            ctx.color_support = colors;
+        }
+        let mut stylesheet = match self.stylesheet.take() {
+            Some(path) => {
+                let file = StylesheetFile::load(path)?;
+                ctx.stylesheet = file.stylesheet().clone();
+                Some(file)
+            }
+            None => None,
+        };
+        self.ui(&mut backend, Block::new()).enter(&mut ctx)?;
+
+        loop {
+            let mut reload_error = None;
+            if let Some(file) = &mut stylesheet {
+                match file.reload_if_changed() {
+                    Ok(true) => {
+                        ctx.stylesheet = file.stylesheet().clone();
+                        ctx.request_redraw();
+                    }
+                    Ok(false) => {}
+                    Err(error) => reload_error = Some(error),
+                }
+            }
+            let event = {
+                let mut ui = self.ui(&mut backend, view(&self.state, &messages));
+                let now = Instant::now();
+                let resized = ctx.resize_timeout() == Some(Duration::ZERO);
+                if now >= next_frame && (ctx.needs_redraw() || resized) {
+                    ui.render(&mut ctx)?;
+                    next_frame = now + self.frame_interval;
+                } else {
+                    // Input still has to find the elements of this tree.
+                    ui.layout(&mut ctx)?;
+                }
+
+                let mut timeout = IDLE_POLL;
+                if let Some(tick) = next_tick {
+                    timeout = timeout.min(tick.saturating_duration_since(now));
+                }
+                if ctx.needs_redraw() {
+                    timeout = timeout.min(next_frame.saturating_duration_since(now));
+                }
+                if let Some(resize) = ctx.resize_timeout() {
+                    timeout = timeout.min(resize);
+                }
+                if stylesheet.is_some() {
+                    timeout = timeout.min(STYLESHEET_POLL);
+                }
+
+                let event = next_event(timeout)?;
+                if let Some(event) = &event {
+                    ctx.process(event);
+                    ui.process(&mut ctx)?;
+                }
+                event
+            };
+
+            let mut events: Vec<_> = messages.take().into_iter().map(AppEvent::Message).collect();
+            events.extend(event.map(AppEvent::Input));
+            events.extend(reload_error.map(AppEvent::StylesheetError));
+            if let (Some(tick), Some(rate)) = (next_tick, self.tick_rate) {
+                let now = Instant::now();
+                if now >= tick {
+                    events.push(AppEvent::Tick);
+                    // Skip ticks that were missed rather than bunching them up.
+                    next_tick = Some((tick + rate).max(now));
+                }
+            }
+            for event in events {
+                ctx.request_redraw();
+                if update(&mut self.state, event) == Action::Quit {
+                    // Draw the final state before handing the screen back.
+                    {
+                        let mut ui = self.ui(&mut backend, view(&self.state, &messages));
+                        ui.render(&mut ctx)?;
+                        ui.exit(&mut ctx)?;
+                    }
+                    return Ok(self.state);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        stylesheet::write_with_mtime,
+        text::Overflow,
+        ui::{AreaShort, Constraint, Direction, Rect, TestBackend, Widget},
+    };
+    use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use std::{collections::VecDeque, fs};
+
+    #[derive(Default)]
+    struct Counter {
+        clicks: u32,
+        ticks: u32,
+    }
+
+    fn mouse(kind: MouseEventKind, column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    /// Runs a click counter through `script`, ticking as often as it can.
+    /// `None` stands for a poll that timed out.
+    fn run_counter(script: Vec<Option<Event>>, frame_rate: u32) -> (Counter, TestBackend) {
+        let mut script = VecDeque::from(script);
+        let mut backend = TestBackend::new(20, 10);
+        let state = App::new(Counter::default())
+            .tick_rate(Duration::ZERO)
+            .frame_rate(frame_rate)
+            .screen(ScreenMode::Fixed(Rect::new((0, 0), (20, 9))))
+            .run_with(
+                &mut backend,
+                Context::with_size((20, 10)),
+                |state, messages| {
+                    let mut root = Block::new();
+                    root.push(
+                        Widget::new(state.clicks.to_string()).on_click(|_| messages.send(())),
+                    );
+                    root
+                },
+                |state, event| match event {
+                    AppEvent::Message(()) => {
+                        state.clicks += 1;
+                        Action::Continue
+                    }
+                    AppEvent::Input(Event::Key(key)) if key.code == KeyCode::Esc => Action::Quit,
+                    AppEvent::Input(_) => Action::Continue,
+                    AppEvent::Tick => {
+                        state.ticks += 1;
+                        Action::Continue
+                    }
+                    AppEvent::StylesheetError(error) => panic!("{error}"),
+                },
+                |_| Ok(script.pop_front().flatten()),
+            )
+            .unwrap();
+        (state, backend)
+    }
+
+    /// A left click on the counter's widget.
+    fn click() -> Vec<Option<Event>> {
+        vec![
+            Some(mouse(MouseEventKind::Down(MouseButton::Left), 4, 4)),
+            Some(mouse(MouseEventKind::Up(MouseButton::Left), 4, 4)),
+        ]
+    }
+
+    #[test]
+    fn runs_view_and_update_until_quit() {
+        let esc = Some(Event::Key(KeyCode::Esc.into()));
+        let script = [click(), click(), vec![None, esc]].concat();
+        let (state, backend) = run_counter(script, u32::MAX);
+        assert_eq!(state.clicks, 2);
+        assert!(state.ticks > 0);
+        assert_eq!(backend.cells()[4][4].symbol, "2");
+    }
+
+    #[test]
+    fn routes_input_between_frames() {
+        let esc = Some(Event::Key(KeyCode::Esc.into()));
+        let script = [click(), vec![esc]].concat();
+        let (state, _) = run_counter(script, 1);
+        assert_eq!(state.clicks, 1);
+    }
+
+    #[test]
+    fn scroll_offset_survives_rebuilding_the_view() {
+        let mut script = VecDeque::from([
+            mouse(MouseEventKind::ScrollDown, 2, 3),
+            mouse(MouseEventKind::ScrollDown, 2, 3),
+            Event::Key(KeyCode::Esc.into()),
+        ]);
+        let mut backend = TestBackend::new(20, 10);
+
+        let scroll = App::new(0)
+            .screen(ScreenMode::Fixed(Rect::new((0, 0), (20, 9))))
+            .run_with(
+                &mut backend,
+                Context::with_size((20, 10)),
+                |scroll, messages| {
+                    let mut root = Block::new().direction(Direction::Vertical);
+                    root.push(
+                        Widget::new("0\n1\n2\n3\n4\n5")
+                            .constraint(Constraint::Fixed(3))
+                            .margin(|m| m.set(AreaShort::Uniform(0)))
+                            .padding(|p| p.set(AreaShort::Uniform(0)))
+                            .overflow(Overflow::Scroll)
+                            .scroll(*scroll)
+                            .on_scroll(|offset, _| messages.send(offset)),
+                    );
+                    root
+                },
+                |scroll, event| match event {
+                    AppEvent::Message(offset) => {
+                        *scroll = offset;
+                        Action::Continue
+                    }
+                    AppEvent::Input(Event::Key(key)) if key.code == KeyCode::Esc => Action::Quit,
+                    _ => Action::Continue,
+                },
+                |_| Ok(script.pop_front()),
+            )
+            .unwrap();
+
+        assert_eq!(scroll, 2);
+        assert_eq!(backend.cells()[2][2].symbol, "2");
+    }
+
+    #[test]
+    fn broken_stylesheet_edits_reach_update() {
+        let path = std::env::temp_dir().join(format!("bad-tui-app-{}.css", std::process::id()));
+        let write = |source, secs| write_with_mtime(&path, source, secs);
+        write("widget { color: red; }", 1);
+        let mut polls = 0;
+
+        let errors = App::new(0)
+            .stylesheet(&path)
+            .run_with(
+                TestBackend::new(20, 10),
+                Context::with_size((20, 10)),
+                |_, _: &Messages<()>| Block::new(),
+                |errors, event| match event {
+                    AppEvent::StylesheetError(_) => {
+                        *errors += 1;
+                        Action::Continue
+                    }
+                    AppEvent::Input(_) => Action::Quit,
+                    _ => Action::Continue,
+                },
+                |_| {
+                    polls += 1;
+                    match polls {
+                        1 => {
+                            write("widget { colour: red; }", 2);
+                            Ok(None)
+                        }
+                        2 => Ok(None),
+                        _ => Ok(Some(Event::Key(KeyCode::Esc.into()))),
+                    }
+                },
+            )
+            .unwrap();
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(errors, 1);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::{
+    style::Style,
+    text::{Align, Line},
+    ui::{Canvas, Pixel, Rect},
+};
+use crossterm::style::Color;
+
+/// The characters a border is drawn with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BorderSymbols {
+    pub horizontal: char,
+    pub vertical: char,
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+}
+
+impl BorderSymbols {
+    pub const PLAIN: Self = Self::lines(['─', '│', '┌', '┐', '└', '┘']);
+    pub const ROUNDED: Self = Self::lines(['─', '│', '╭', '╮', '╰', '╯']);
+    pub const DOUBLE: Self = Self::lines(['═', '║', '╔', '╗', '╚', '╝']);
+    pub const THICK: Self = Self::lines(['━', '┃', '┏', '┓', '┗', '┛']);
+    pub const ASCII: Self = Self::lines(['-', '|', '+', '+', '+', '+']);
+
+    const fn lines(
+        [horizontal, vertical, top_left, top_right, bottom_left, bottom_right]: [char; 6],
+    ) -> Self {
+        Self {
+            horizontal,
+            vertical,
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+        }
+    }
+
+    /// The same character all around.
+    pub const fn uniform(symbol: char) -> Self {
+        Self::lines([symbol; 6])
+    }
+
+    fn contains(&self, symbol: char) -> bool {
+        [
+            self.horizontal,
+            self.vertical,
+            self.top_left,
+            self.top_right,
+            self.bottom_left,
+            self.bottom_right,
+        ]
+        .contains(&symbol)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BorderStyle {
+    /// No border. The sides take no room.
+    None,
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+    Ascii,
+    Custom(BorderSymbols),
+}
+
+impl BorderStyle {
+    pub fn symbols(self) -> Option<BorderSymbols> {
+        match self {
+            BorderStyle::None => None,
+            BorderStyle::Plain => Some(BorderSymbols::PLAIN),
+            BorderStyle::Rounded => Some(BorderSymbols::ROUNDED),
+            BorderStyle::Double => Some(BorderSymbols::DOUBLE),
+            BorderStyle::Thick => Some(BorderSymbols::THICK),
+            BorderStyle::Ascii => Some(BorderSymbols::ASCII),
+            BorderStyle::Custom(symbols) => Some(symbols),
+        }
+    }
+}
+
+/// Which sides of a rectangle something applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sides {
+    pub top: bool,
+    pub right: bool,
+    pub bottom: bool,
+    pub left: bool,
+}
+
+impl Sides {
+    pub const ALL: Self = Self {
+        top: true,
+        right: true,
+        bottom: true,
+        left: true,
+    };
+    pub const NONE: Self = Self {
+        top: false,
+        right: false,
+        bottom: false,
+        left: false,
+    };
+}
+
+impl Default for Sides {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TitlePosition {
+    #[default]
+    Top,
+    Bottom,
+}
+
+/// Text drawn over the top or bottom border, between the corners.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Title {
+    pub text: Line,
+    pub position: TitlePosition,
+    /// `Justify` is the same as `Left`.
+    pub align: Align,
+}
+
+impl Title {
+    pub fn new<L: Into<Line>>(text: L) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn position(mut self, position: TitlePosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+}
+
+impl<T: Into<Line>> From<T> for Title {
+    fn from(text: T) -> Self {
+        Title::new(text)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Border {
+    pub style: BorderStyle,
+    pub sides: Sides,
+    /// Laid over the theme's border style for the lines and titles.
+    pub line_style: Style,
+    pub titles: Vec<Title>,
+    /// Whether to share the border with neighbouring blocks that also merge
+    /// theirs. See `Border::merge`.
+    pub merge: bool,
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Self {
+            style: BorderStyle::Custom(BorderSymbols::uniform('#')),
+            sides: Sides::ALL,
+            line_style: Style::default(),
+            titles: vec![],
+            merge: false,
+        }
+    }
+}
+
+impl Border {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn style(mut self, style: BorderStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.line_style = self.line_style.fg(color);
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Self {
+        self.line_style = self.line_style.bg(background);
+        self
+    }
+
+    pub fn line_style(mut self, style: Style) -> Self {
+        self.line_style = style;
+        self
+    }
+
+    /// Adds a title. Titles on a side without a border are not drawn, and
+    /// later titles are drawn over earlier ones where they meet.
+    pub fn title<T: Into<Title>>(mut self, title: T) -> Self {
+        self.titles.push(title.into());
+        self
+    }
+
+    /// Lays the border over that of the previous and next sibling block
+    /// when both merge, so they share one line. Where lines meet they are
+    /// joined with the right junction (`┬`, `┼`, ...).
+    pub fn merge(mut self, merge: bool) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    /// Cells the border takes on each side: top, right, bottom, left.
+    pub fn thickness(&self) -> (u16, u16, u16, u16) {
+        if self.style == BorderStyle::None {
+            return (0, 0, 0, 0);
+        }
+        let Sides {
+            top,
+            right,
+            bottom,
+            left,
+        } = self.sides;
+        (top.into(), right.into(), bottom.into(), left.into())
+    }
+
+    /// Draws the border around the edge of `rect`, joining it with any
+    /// lines of the same kind already there.
+    pub fn render(&self, canvas: &mut Canvas, rect: Rect) {
+        let Some(symbols) = self.style.symbols() else {
+            return;
+        };
+        if rect.is_empty() {
+            return;
+        }
+        let style = canvas.theme().border.patch(self.line_style);
+        let Sides {
+            top,
+            right,
+            bottom,
+            left,
+        } = self.sides;
+        let (last_col, last_row) = (rect.right() - 1, rect.bottom() - 1);
+        for row in rect.row..rect.bottom() {
+            let edge_row = row == rect.row || row == last_row;
+            let cols: Vec<u16> = if edge_row {
+                (rect.col..rect.right()).collect()
+            } else {
+                vec![rect.col, last_col]
+            };
+            for col in cols {
+                let t = top && row == rect.row;
+                let b = bottom && row == last_row;
+                let l = left && col == rect.col;
+                let r = right && col == last_col;
+                let symbol = match (t, r, b, l) {
+                    (true, _, _, true) => symbols.top_left,
+                    (true, true, _, _) => symbols.top_right,
+                    (_, _, true, true) => symbols.bottom_left,
+                    (_, true, true, _) => symbols.bottom_right,
+                    (true, _, _, _) | (_, _, true, _) => symbols.horizontal,
+                    (_, true, _, _) | (_, _, _, true) => symbols.vertical,
+                    _ => continue,
+                };
+                let symbol = match canvas.get(col, row) {
+                    Some(px) => merge(&px.symbol, symbol, &symbols),
+                    None => symbol,
+                };
+                canvas.put(col, row, Pixel::styled(symbol, style));
+            }
+        }
+
+        // Titles go between the corners.
+        let span = rect.inset(0, right.into(), 0, left.into());
+        for title in &self.titles {
+            let row = match title.position {
+                TitlePosition::Top if top => rect.row,
+                TitlePosition::Bottom if bottom => last_row,
+                _ => continue,
+            };
+            let free = span.width.saturating_sub(title.text.width());
+            let col = span.col
+                + match title.align {
+                    Align::Left | Align::Justify => 0,
+                    Align::Center => free / 2,
+                    Align::Right => free,
+                };
+            let mut canvas = canvas.clipped(Rect::new((span.col, row), (span.width, 1)));
+            let base = Pixel::styled(' ', style);
+            let mut col = col;
+            for text in &title.text.spans {
+                let mut px = base.clone();
+                text.style.apply(&mut px);
+                col = canvas.print_styled(col, row, &text.content, &px);
+            }
+        }
+    }
+}
+
+const UP: u8 = 1;
+const RIGHT: u8 = 2;
+const DOWN: u8 = 4;
+const LEFT: u8 = 8;
+
+/// Which way each symbol of a line set connects, in the order of the sets.
+const CONNECTIONS: [u8; 11] = [
+    RIGHT | LEFT,
+    UP | DOWN,
+    RIGHT | DOWN,
+    DOWN | LEFT,
+    UP | RIGHT,
+    UP | LEFT,
+    UP | RIGHT | DOWN,
+    UP | DOWN | LEFT,
+    RIGHT | DOWN | LEFT,
+    UP | RIGHT | LEFT,
+    UP | RIGHT | DOWN | LEFT,
+];
+const LIGHT: [char; 11] = ['─', '│', '┌', '┐', '└', '┘', '├', '┤', '┬', '┴', '┼'];
+const HEAVY: [char; 11] = ['━', '┃', '┏', '┓', '┗', '┛', '┣', '┫', '┳', '┻', '╋'];
+const DOUBLE: [char; 11] = ['═', '║', '╔', '╗', '╚', '╝', '╠', '╣', '╦', '╩', '╬'];
+const ROUNDED_CORNERS: [(char, u8); 4] = [
+    ('╭', RIGHT | DOWN),
+    ('╮', DOWN | LEFT),
+    ('╰', UP | RIGHT),
+    ('╯', UP | LEFT),
+];
+
+fn connections(symbol: char) -> Option<u8> {
+    for set in [&LIGHT, &HEAVY, &DOUBLE] {
+        if let Some(i) = set.iter().position(|&c| c == symbol) {
+            return Some(CONNECTIONS[i]);
+        }
+    }
+    ROUNDED_CORNERS
+        .iter()
+        .find(|(c, _)| *c == symbol)
+        .map(|(_, bits)| *bits)
+}
+
+/// What to draw when `symbol` of `symbols` lands on a cell showing
+/// `existing`. Box-drawing lines are joined into a junction of the new
+/// line's weight. Other borders fall back to their corner symbol where two
+/// of their sides cross.
+fn merge(existing: &str, symbol: char, symbols: &BorderSymbols) -> char {
+    let mut chars = existing.chars();
+    let (Some(existing), None) = (chars.next(), chars.next()) else {
+        return symbol;
+    };
+    if existing == symbol {
+        return symbol;
+    }
+    let set = match symbols.horizontal {
+        '─' => &LIGHT,
+        '━' => &HEAVY,
+        '═' => &DOUBLE,
+        _ if symbols.contains(existing) => return symbols.top_left,
+        _ => return symbol,
+    };
+    let (Some(old), Some(new)) = (connections(existing), connections(symbol)) else {
+        return symbol;
+    };
+    CONNECTIONS
+        .iter()
+        .position(|&bits| bits == old | new)
+        .map_or(symbol, |i| set[i])
+}
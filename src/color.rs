@@ -0,0 +1,318 @@
+use crate::ui::Pixel;
+use crossterm::style::{Attribute, Color};
+use std::{borrow::Cow, env, fs, path::PathBuf};
+
+/// How many colors the terminal can show. Colors it cannot show are mapped
+/// to the nearest one it can when the frame is flushed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// No colors. Cells with a background are drawn in reverse video
+    /// instead, so highlighted parts still stand out.
+    Monochrome,
+    /// The 16 named colors.
+    Ansi16,
+    /// The 256-color palette.
+    Ansi256,
+    /// Any `Color::Rgb`.
+    #[default]
+    TrueColor,
+}
+
+/// The 16 named colors in palette order, with xterm's values for them.
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each channel in the 6x6x6 cube of the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+    /// Works out what the terminal supports from `NO_COLOR`, `COLORTERM`,
+    /// `TERM` and the terminfo entry for `TERM`, in that order.
+    pub fn detect() -> Self {
+        Self::from_env(|name| env::var(name).ok(), terminfo_colors)
+    }
+
+    /// `detect` with the environment and terminfo lookup passed in.
+    fn from_env<V, T>(var: V, terminfo: T) -> Self
+    where
+        V: Fn(&str) -> Option<String>,
+        T: Fn(&str) -> Option<i32>,
+    {
+        // https://no-color.org: set and not empty.
+        if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return ColorSupport::Monochrome;
+        }
+        if let Some("truecolor" | "24bit") = var("COLORTERM").as_deref() {
+            return ColorSupport::TrueColor;
+        }
+        let Some(term) = var("TERM").filter(|term| !term.is_empty()) else {
+            // Windows consoles do not set TERM and handle every color.
+            return if cfg!(windows) {
+                ColorSupport::TrueColor
+            } else {
+                ColorSupport::Ansi16
+            };
+        };
+        if term == "dumb" {
+            return ColorSupport::Monochrome;
+        }
+        if term.ends_with("-direct") || term.ends_with("-truecolor") {
+            return ColorSupport::TrueColor;
+        }
+        if term.ends_with("-256color") {
+            return ColorSupport::Ansi256;
+        }
+        match terminfo(&term) {
+            Some(colors) if colors >= 1 << 24 => ColorSupport::TrueColor,
+            Some(colors) if colors >= 256 => ColorSupport::Ansi256,
+            Some(colors) if colors >= 8 => ColorSupport::Ansi16,
+            Some(_) => ColorSupport::Monochrome,
+            None => ColorSupport::Ansi16,
+        }
+    }
+
+    /// The nearest color to `color` that this level can show.
+    pub fn downsample(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::TrueColor, _) | (_, Color::Reset) => color,
+            (ColorSupport::Monochrome, _) => Color::Reset,
+            (ColorSupport::Ansi256, Color::Rgb { r, g, b }) => {
+                Color::AnsiValue(nearest_256((r, g, b)))
+            }
+            (ColorSupport::Ansi256, _) => color,
+            (ColorSupport::Ansi16, Color::Rgb { r, g, b }) => nearest_16((r, g, b)),
+            (ColorSupport::Ansi16, Color::AnsiValue(index)) => match ANSI16.get(index as usize) {
+                Some((named, _)) => *named,
+                None => nearest_16(palette_rgb(index)),
+            },
+            (ColorSupport::Ansi16, _) => color,
+        }
+    }
+
+    /// `px` as this level can show it.
+    pub(crate) fn adapt(self, px: &Pixel) -> Cow<'_, Pixel> {
+        if self == ColorSupport::TrueColor {
+            return Cow::Borrowed(px);
+        }
+        let mut adapted = px.clone();
+        adapted.color = self.downsample(px.color);
+        adapted.background = self.downsample(px.background);
+        adapted.underline_color = px
+            .underline_color
+            .map(|color| self.downsample(color))
+            .filter(|color| *color != Color::Reset);
+        // Cells that are reversed already, like the focused element, stay
+        // that way rather than being flipped back.
+        if self == ColorSupport::Monochrome && px.background != Color::Reset {
+            adapted.attributes.set(Attribute::Reverse);
+        }
+        Cow::Owned(adapted)
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, named)| distance(rgb, *named))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+/// The nearest entry of the cube or the gray ramp; the first 16 entries
+/// depend on the terminal's theme, so they are left out.
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = ((u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3) as u8;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    if distance(rgb, palette_rgb(gray)) < distance(rgb, palette_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// The usual value of entry `index` of the 256-color palette.
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize].1,
+        16..=231 => {
+            let i = index - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// The `colors` capability of `term`'s compiled terminfo entry.
+fn terminfo_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    let mut dirs: Vec<PathBuf> = vec![];
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        dirs.extend(list.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+    }
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .into_iter()
+            .map(PathBuf::from),
+    );
+    dirs.iter()
+        .flat_map(|dir| {
+            // Entries are filed under their first letter, or its hex code
+            // on some systems.
+            [first.to_string(), format!("{:x}", first as u32)].map(|sub| dir.join(sub).join(term))
+        })
+        .find_map(|path| fs::read(path).ok())
+        .and_then(|data| max_colors(&data))
+}
+
+/// Reads `max_colors` from a compiled terminfo entry, in the legacy or the
+/// 32-bit number format.
+fn max_colors(data: &[u8]) -> Option<i32> {
+    const MAX_COLORS: usize = 13;
+    let short = |i: usize| -> Option<usize> {
+        let bytes = data.get(i * 2..i * 2 + 2)?;
+        Some(i16::from_le_bytes([bytes[0], bytes[1]]).max(0) as usize)
+    };
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, numbers) = (short(1)?, short(2)?, short(3)?);
+    if MAX_COLORS >= numbers {
+        return None;
+    }
+    let mut start = 12 + names + bools;
+    // Numbers start on an even byte.
+    start += start % 2;
+    let at = start + MAX_COLORS * number_size;
+    let colors = match number_size {
+        2 => i32::from(i16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?)),
+        _ => i32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?),
+    };
+    (colors >= 0).then_some(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_support_from_the_environment() {
+        let detect = |vars: &[(&str, &str)], terminfo: Option<i32>| {
+            ColorSupport::from_env(
+                |name| {
+                    vars.iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, v)| v.to_string())
+                },
+                |_| terminfo,
+            )
+        };
+        let truecolor = [("COLORTERM", "truecolor"), ("TERM", "xterm")];
+        assert_eq!(detect(&truecolor, None), ColorSupport::TrueColor);
+        let no_color = [("NO_COLOR", "1"), ("COLORTERM", "truecolor")];
+        assert_eq!(detect(&no_color, None), ColorSupport::Monochrome);
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color")], None),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(detect(&[("TERM", "dumb")], None), ColorSupport::Monochrome);
+        assert_eq!(detect(&[("TERM", "vt100")], None), ColorSupport::Ansi16);
+        assert_eq!(
+            detect(&[("TERM", "vt100")], Some(2)),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(
+            detect(&[("TERM", "foot")], Some(256)),
+            ColorSupport::Ansi256
+        );
+    }
+
+    #[test]
+    fn reads_max_colors_from_terminfo() {
+        // Header, "x|y\0" as names, one boolean, padding, then 14 numbers.
+        let mut entry: Vec<u8> = [0o432, 4, 1, 14, 0, 0]
+            .iter()
+            .flat_map(|n: &i16| n.to_le_bytes())
+            .collect();
+        entry.extend(b"x|y\0");
+        entry.extend([1, 0]);
+        for n in 0..14i16 {
+            let colors: i16 = if n == 13 { 256 } else { -1 };
+            entry.extend(colors.to_le_bytes());
+        }
+        assert_eq!(max_colors(&entry), Some(256));
+        assert_eq!(max_colors(&entry[..20]), None);
+    }
+
+    #[test]
+    fn downsamples_to_the_nearest_color() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        };
+        assert_eq!(ColorSupport::TrueColor.downsample(orange), orange);
+        assert_eq!(
+            ColorSupport::Ansi256.downsample(orange),
+            Color::AnsiValue(208)
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.downsample(Color::Rgb {
+                r: 128,
+                g: 128,
+                b: 130
+            }),
+            Color::AnsiValue(244)
+        );
+        assert_eq!(ColorSupport::Ansi16.downsample(orange), Color::DarkYellow);
+        assert_eq!(
+            ColorSupport::Ansi16.downsample(Color::AnsiValue(12)),
+            Color::Blue
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.downsample(Color::AnsiValue(232)),
+            Color::Black
+        );
+        assert_eq!(
+            ColorSupport::Monochrome.downsample(Color::Red),
+            Color::Reset
+        );
+    }
+}
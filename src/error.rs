@@ -0,0 +1,42 @@
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Writing to or reading from the terminal failed.
+    Io(io::Error),
+    /// There is no terminal to draw on, e.g. because output is not a TTY.
+    TerminalUnavailable(io::Error),
+    /// The tree measures larger than a terminal could ever be.
+    LayoutOverflow,
+    /// An element was set up with values that make no sense, e.g. a
+    /// percentage over 100.
+    InvalidConfig(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "terminal i/o failed: {e}"),
+            Error::TerminalUnavailable(e) => write!(f, "no terminal available: {e}"),
+            Error::LayoutOverflow => write!(f, "layout is too large to arrange"),
+            Error::InvalidConfig(reason) => write!(f, "invalid element config: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::TerminalUnavailable(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,258 @@
+use crate::focus::ElementPath;
+use crate::ui::Element;
+use crossterm::event::{KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use std::time::{Duration, Instant};
+
+/// An input event on its way through the element tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UiEvent {
+    Mouse(MouseEvent),
+    Key(KeyEvent),
+    Paste(String),
+    /// The target just got keyboard focus.
+    Focus,
+    /// The target just lost keyboard focus.
+    Blur,
+    /// The pointer just moved onto the target.
+    Enter,
+    /// The pointer just moved off the target.
+    Leave,
+    /// A button went down and came back up on the target. `count` is 2 for
+    /// a double click, 3 for a triple click and so on.
+    Click {
+        button: MouseButton,
+        count: u8,
+        column: u16,
+        row: u16,
+    },
+}
+
+/// Where an event is on its way from the root to the target and back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// On an ancestor of the target, on the way down.
+    Capture,
+    /// On the target itself.
+    Target,
+    /// On an ancestor of the target, on the way back up.
+    Bubble,
+}
+
+/// What an element's `handle_event` gets: the event, how far along its route
+/// it is, and a way to stop it from going any further.
+#[derive(Debug)]
+pub struct EventContext {
+    pub event: UiEvent,
+    pub phase: Phase,
+    /// The element the event is aimed at.
+    pub target: ElementPath,
+    handled: bool,
+    redraw: bool,
+}
+
+impl EventContext {
+    pub fn new(event: UiEvent, target: ElementPath) -> Self {
+        Self {
+            event,
+            phase: Phase::Capture,
+            target,
+            handled: false,
+            redraw: false,
+        }
+    }
+
+    /// Marks the event as handled, so no other element sees it.
+    pub fn stop_propagation(&mut self) {
+        self.handled = true;
+    }
+
+    pub fn is_handled(&self) -> bool {
+        self.handled
+    }
+
+    /// Asks for the next frame to be drawn, e.g. after a handler changed
+    /// state the view depends on.
+    pub fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    pub fn redraw_requested(&self) -> bool {
+        self.redraw
+    }
+}
+
+type Callback<'a> = Box<dyn FnMut(&mut EventContext) + 'a>;
+type KeyCallback<'a> = Box<dyn FnMut(&KeyEvent, &mut EventContext) + 'a>;
+pub(crate) type ScrollCallback<'a> = Box<dyn FnMut(u16, &mut EventContext) + 'a>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Everything known about the mouse, kept up to date by `Context::process`.
+#[derive(Clone, Debug)]
+pub struct MouseState {
+    /// Where the pointer was last seen.
+    pub position: Option<(u16, u16)>,
+    /// The buttons being held down.
+    pub buttons: Vec<MouseButton>,
+    /// Which way the wheel turned, if the event being processed is a scroll.
+    pub scroll: Option<ScrollDirection>,
+    /// The modifier keys held during the last mouse event.
+    pub modifiers: KeyModifiers,
+    /// How many times in a row the last button was pressed: 1 for a single
+    /// click, 2 for a double click and so on.
+    pub click_count: u8,
+    /// How soon a press has to follow the last one, on the same spot and
+    /// with the same button, to count as a multi-click.
+    pub multi_click_interval: Duration,
+    last_press: Option<(MouseButton, (u16, u16), Instant)>,
+}
+
+impl Default for MouseState {
+    fn default() -> Self {
+        Self {
+            position: None,
+            buttons: vec![],
+            scroll: None,
+            modifiers: KeyModifiers::NONE,
+            click_count: 0,
+            multi_click_interval: Duration::from_millis(400),
+            last_press: None,
+        }
+    }
+}
+
+impl MouseState {
+    pub fn is_down(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn update(&mut self, event: &MouseEvent) {
+        let pos = (event.column, event.row);
+        self.position = Some(pos);
+        self.modifiers = event.modifiers;
+        self.scroll = match event.kind {
+            MouseEventKind::ScrollUp => Some(ScrollDirection::Up),
+            MouseEventKind::ScrollDown => Some(ScrollDirection::Down),
+            MouseEventKind::ScrollLeft => Some(ScrollDirection::Left),
+            MouseEventKind::ScrollRight => Some(ScrollDirection::Right),
+            _ => None,
+        };
+        match event.kind {
+            MouseEventKind::Down(button) => {
+                if !self.is_down(button) {
+                    self.buttons.push(button);
+                }
+                let now = Instant::now();
+                self.click_count = match self.last_press {
+                    Some((last, at, when))
+                        if last == button
+                            && at == pos
+                            && now.duration_since(when) <= self.multi_click_interval =>
+                    {
+                        self.click_count.saturating_add(1)
+                    }
+                    _ => 1,
+                };
+                self.last_press = Some((button, pos, now));
+            }
+            MouseEventKind::Up(button) => self.buttons.retain(|b| *b != button),
+            _ => {}
+        }
+    }
+}
+
+/// The closures set with `on_click`, `on_hover`, `on_mouse_leave` and
+/// `on_key` on `Block` and `Widget`. Clicks and keys are seen on the target
+/// and while bubbling, so a block hears about its children; hovering is only
+/// reported to the element the pointer is on.
+#[derive(Default)]
+pub struct Handlers<'a> {
+    pub on_click: Option<Callback<'a>>,
+    pub on_hover: Option<Callback<'a>>,
+    pub on_mouse_leave: Option<Callback<'a>>,
+    pub on_key: Option<KeyCallback<'a>>,
+}
+
+impl Handlers<'_> {
+    /// Runs whichever closure `cx` calls for.
+    pub fn handle(&mut self, cx: &mut EventContext) {
+        let target = cx.phase == Phase::Target;
+        match &cx.event {
+            _ if cx.phase == Phase::Capture => {}
+            UiEvent::Click {
+                button: MouseButton::Left,
+                ..
+            } => self.click(cx),
+            UiEvent::Key(key) => {
+                let key = *key;
+                if let Some(f) = &mut self.on_key {
+                    f(&key, cx);
+                }
+            }
+            UiEvent::Enter if target => {
+                if let Some(f) = &mut self.on_hover {
+                    f(cx);
+                }
+            }
+            UiEvent::Leave if target => {
+                if let Some(f) = &mut self.on_mouse_leave {
+                    f(cx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs the click handler, if any.
+    pub fn click(&mut self, cx: &mut EventContext) {
+        if let Some(f) = &mut self.on_click {
+            f(cx);
+        }
+    }
+}
+
+/// Sends `cx` down `path` from `el`, capturing at every ancestor on the way,
+/// then to the target, then back up through the ancestors, until a handler
+/// stops it.
+pub fn dispatch(el: &mut dyn Element, path: &[usize], cx: &mut EventContext) {
+    let Some((head, rest)) = path.split_first() else {
+        cx.phase = Phase::Target;
+        el.handle_event(cx);
+        return;
+    };
+
+    cx.phase = Phase::Capture;
+    el.handle_event(cx);
+    if cx.is_handled() {
+        return;
+    }
+    if let Some(child) = el.child_mut(*head) {
+        dispatch(child, rest, cx);
+        if cx.is_handled() {
+            return;
+        }
+    }
+    cx.phase = Phase::Bubble;
+    el.handle_event(cx);
+}
+
+/// The path to the innermost element under `pos`. Later siblings are drawn
+/// on top, so they are checked first.
+pub fn hit_test(el: &dyn Element, pos: (u16, u16)) -> Option<ElementPath> {
+    if !el.rect().contains(pos) {
+        return None;
+    }
+    for i in (0..el.child_count()).rev() {
+        if let Some(mut path) = el.child(i).and_then(|child| hit_test(child, pos)) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    el.hit(pos).then(Vec::new)
+}
@@ -0,0 +1,147 @@
+use crate::ui::{Element, Rect};
+
+/// Where an element sits in the tree: the child index at every level, from
+/// the root down. Paths stay valid as long as the tree keeps its shape, even
+/// if it is rebuilt from scratch.
+pub type ElementPath = Vec<usize>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FocusDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Keeps track of which element has keyboard focus. Focusable elements are
+/// registered in tree order every frame, which is the order Tab walks.
+#[derive(Clone, Debug, Default)]
+pub struct FocusManager {
+    order: Vec<(ElementPath, Rect)>,
+    focused: Option<ElementPath>,
+}
+
+impl FocusManager {
+    pub fn focused(&self) -> Option<&[usize]> {
+        self.focused.as_deref()
+    }
+
+    pub fn is_focused(&self, path: &[usize]) -> bool {
+        self.focused() == Some(path)
+    }
+
+    /// Focuses the element at `path`, if it is registered as focusable.
+    pub fn focus(&mut self, path: &[usize]) -> bool {
+        if self.order.iter().any(|(p, _)| p == path) {
+            self.focused = Some(path.to_vec());
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn blur(&mut self) {
+        self.focused = None;
+    }
+
+    pub fn register(&mut self, path: ElementPath, rect: Rect) {
+        self.order.push((path, rect));
+    }
+
+    /// Moves focus to the next focusable element, wrapping around.
+    pub fn focus_next(&mut self) {
+        self.step(1);
+    }
+
+    /// Moves focus to the previous focusable element, wrapping around.
+    pub fn focus_prev(&mut self) {
+        self.step(-1);
+    }
+
+    fn step(&mut self, by: isize) {
+        if self.order.is_empty() {
+            return;
+        }
+        let len = self.order.len() as isize;
+        let next = match self.position() {
+            Some(i) => (i as isize + by).rem_euclid(len),
+            None if by > 0 => 0,
+            None => len - 1,
+        };
+        self.focused = Some(self.order[next as usize].0.clone());
+    }
+
+    fn position(&self) -> Option<usize> {
+        let focused = self.focused.as_ref()?;
+        self.order.iter().position(|(p, _)| p == focused)
+    }
+
+    /// Moves focus to the nearest focusable element on screen in `direction`.
+    /// Returns whether focus moved.
+    pub fn focus_towards(&mut self, direction: FocusDirection) -> bool {
+        let Some(from) = self.position().map(|i| self.order[i].1) else {
+            self.focus_next();
+            return self.focused.is_some();
+        };
+        let center = |r: Rect| {
+            (
+                i32::from(r.col) * 2 + i32::from(r.width),
+                i32::from(r.row) * 2 + i32::from(r.height),
+            )
+        };
+        let (fx, fy) = center(from);
+
+        let best = self
+            .order
+            .iter()
+            .filter_map(|(path, rect)| {
+                let (x, y) = center(*rect);
+                let (along, across) = match direction {
+                    FocusDirection::Up => (fy - y, x - fx),
+                    FocusDirection::Down => (y - fy, x - fx),
+                    FocusDirection::Left => (fx - x, y - fy),
+                    FocusDirection::Right => (x - fx, y - fy),
+                };
+                // Prefer elements straight ahead over ones off to the side.
+                (along > 0).then(|| (along + across.abs() * 2, path))
+            })
+            .min_by_key(|(score, _)| *score);
+
+        match best {
+            Some((_, path)) => {
+                self.focused = Some(path.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Re-registers every focusable element in `root`. Focus is dropped if
+    /// the focused element is gone.
+    pub(crate) fn rebuild(&mut self, root: &dyn Element) {
+        self.order.clear();
+        collect_focusable(root, &mut vec![], self);
+        if self.position().is_none() {
+            self.focused = None;
+        }
+    }
+
+    /// Focuses `path` or, failing that, its closest focusable ancestor.
+    /// Returns whether anything got focus.
+    pub(crate) fn focus_within(&mut self, path: &[usize]) -> bool {
+        (0..=path.len()).rev().any(|len| self.focus(&path[..len]))
+    }
+}
+
+fn collect_focusable(el: &dyn Element, path: &mut ElementPath, focus: &mut FocusManager) {
+    if el.focusable() {
+        focus.register(path.clone(), el.rect());
+    }
+    for i in 0..el.child_count() {
+        if let Some(child) = el.child(i) {
+            path.push(i);
+            collect_focusable(child, path, focus);
+            path.pop();
+        }
+    }
+}
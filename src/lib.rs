@@ -1,3 +1,13 @@
+pub mod app;
+pub mod border;
+pub mod color;
+pub mod error;
+pub mod event;
+pub mod focus;
+pub mod style;
+pub mod stylesheet;
+pub mod terminal;
+pub mod text;
 pub mod ui;
 
 pub fn add(left: usize, right: usize) -> usize {
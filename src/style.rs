@@ -0,0 +1,134 @@
+use crate::ui::Pixel;
+use crossterm::style::{Attribute, Attributes, Color};
+
+/// Colors and attributes to lay over a cell. `None` leaves the cell's
+/// color alone, so styles can be stacked with `patch`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    /// Added to whatever the cell already has.
+    pub attributes: Attributes,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    pub fn bg(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    pub fn attribute(mut self, attribute: Attribute) -> Self {
+        self.attributes.set(attribute);
+        self
+    }
+
+    /// `other` on top of `self`: its colors win where it has them, and the
+    /// attributes of both apply.
+    pub fn patch(self, other: Style) -> Self {
+        Self {
+            fg: other.fg.or(self.fg),
+            bg: other.bg.or(self.bg),
+            attributes: self.attributes | other.attributes,
+        }
+    }
+
+    /// Styles `px` in place.
+    pub fn apply(&self, px: &mut Pixel) {
+        if let Some(fg) = self.fg {
+            px.color = fg;
+        }
+        if let Some(bg) = self.bg {
+            px.background = bg;
+        }
+        px.attributes = px.attributes | self.attributes;
+    }
+}
+
+/// Parses a color name (`red`, `dark_blue`, `reset`, ...), an ANSI palette
+/// index (`0` to `255`) or a hex code (`#ff8800`).
+pub fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    if let Ok(index) = name.parse::<u8>() {
+        return Some(Color::AnsiValue(index));
+    }
+    match name.to_lowercase().replace('-', "_").as_str() {
+        "reset" | "default" => Some(Color::Reset),
+        "gray" => Some(Color::Grey),
+        "dark_gray" => Some(Color::DarkGrey),
+        name => Color::try_from(name).ok(),
+    }
+}
+
+/// Parses an attribute name as used in markup: `b`/`bold`, `d`/`dim`,
+/// `i`/`italic`, `u`/`underline`, `blink`, `r`/`reverse`, `s`/`strike`.
+pub fn parse_attribute(name: &str) -> Option<Attribute> {
+    Some(match name {
+        "b" | "bold" => Attribute::Bold,
+        "d" | "dim" => Attribute::Dim,
+        "i" | "italic" => Attribute::Italic,
+        "u" | "underline" => Attribute::Underlined,
+        "uu" | "double_underline" => Attribute::DoubleUnderlined,
+        "curly" | "undercurl" => Attribute::Undercurled,
+        "blink" => Attribute::SlowBlink,
+        "r" | "reverse" => Attribute::Reverse,
+        "s" | "strike" | "strikethrough" => Attribute::CrossedOut,
+        _ => return None,
+    })
+}
+
+/// Styles for the roles elements play, so an app can restyle every element
+/// in one place. Elements start from their role and lay their own style on
+/// top.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Widget text.
+    pub primary: Style,
+    /// Backgrounds, such as the inside of blocks and widget margins.
+    pub surface: Style,
+    pub border: Style,
+    /// Laid over an element with keyboard focus.
+    pub focused: Style,
+    /// Laid over the element under the pointer.
+    pub hovered: Style,
+    /// Laid over an element while a mouse button is held on it.
+    pub pressed: Style,
+    /// Replaces the interaction styles of disabled widgets.
+    pub disabled: Style,
+    pub error: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            primary: Style::new().fg(Color::Black).bg(Color::White),
+            surface: Style::new().fg(Color::White).bg(Color::Reset),
+            border: Style::new().fg(Color::White).bg(Color::Reset),
+            focused: Style::new()
+                .attribute(Attribute::Reverse)
+                .attribute(Attribute::Bold),
+            hovered: Style::new(),
+            pressed: Style::new().fg(Color::Red),
+            disabled: Style::new().fg(Color::DarkGrey),
+            error: Style::new().fg(Color::Red).attribute(Attribute::Bold),
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use crate::{
+    border::BorderStyle,
+    error::{Error, Result},
+    style::{parse_attribute, parse_color, Style},
+    text::{Align, VerticalAlign},
+    ui::{AreaShort, Element},
+};
+use crossterm::style::Color;
+use std::{fs, path::PathBuf, time::SystemTime};
+
+/// What a stylesheet sets on an element. `None` leaves the element's own
+/// setting alone; where a stylesheet does set something, it wins over what
+/// the code set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Properties {
+    /// `color`, `background` and `text-style`.
+    pub style: Style,
+    pub border: Option<BorderStyle>,
+    pub border_color: Option<Color>,
+    pub padding: Option<AreaShort>,
+    pub margin: Option<AreaShort>,
+    /// `text-align`.
+    pub align: Option<Align>,
+    pub vertical_align: Option<VerticalAlign>,
+}
+
+impl Properties {
+    /// `other` on top of `self`.
+    pub fn patch(self, other: Properties) -> Self {
+        Self {
+            style: self.style.patch(other.style),
+            border: other.border.or(self.border),
+            border_color: other.border_color.or(self.border_color),
+            padding: other.padding.or(self.padding),
+            margin: other.margin.or(self.margin),
+            align: other.align.or(self.align),
+            vertical_align: other.vertical_align.or(self.vertical_align),
+        }
+    }
+
+    /// Sets the property `name` from its stylesheet `value`.
+    fn set(&mut self, name: &str, value: &str) -> std::result::Result<(), String> {
+        let color = |value: &str| parse_color(value).ok_or(format!("unknown color {value:?}"));
+        match name {
+            "color" => self.style = self.style.fg(color(value)?),
+            "background" | "background-color" => self.style = self.style.bg(color(value)?),
+            "text-style" => {
+                for word in value.split_whitespace().filter(|w| *w != "none") {
+                    let attribute =
+                        parse_attribute(word).ok_or(format!("unknown text style {word:?}"))?;
+                    self.style = self.style.attribute(attribute);
+                }
+            }
+            "border" => {
+                for word in value.split_whitespace() {
+                    match parse_border(word) {
+                        Some(border) => self.border = Some(border),
+                        None => self.border_color = Some(color(word)?),
+                    }
+                }
+            }
+            "border-color" => self.border_color = Some(color(value)?),
+            "padding" => self.padding = Some(parse_area(value)?),
+            "margin" => self.margin = Some(parse_area(value)?),
+            "text-align" => {
+                self.align = Some(match value {
+                    "left" => Align::Left,
+                    "center" => Align::Center,
+                    "right" => Align::Right,
+                    "justify" => Align::Justify,
+                    _ => return Err(format!("unknown text-align {value:?}")),
+                })
+            }
+            "vertical-align" => {
+                self.vertical_align = Some(match value {
+                    "top" => VerticalAlign::Top,
+                    "middle" => VerticalAlign::Middle,
+                    "bottom" => VerticalAlign::Bottom,
+                    _ => return Err(format!("unknown vertical-align {value:?}")),
+                })
+            }
+            _ => return Err(format!("unknown property {name:?}")),
+        }
+        Ok(())
+    }
+}
+
+fn parse_border(name: &str) -> Option<BorderStyle> {
+    Some(match name {
+        "none" => BorderStyle::None,
+        "plain" => BorderStyle::Plain,
+        "rounded" => BorderStyle::Rounded,
+        "double" => BorderStyle::Double,
+        "thick" => BorderStyle::Thick,
+        "ascii" => BorderStyle::Ascii,
+        _ => return None,
+    })
+}
+
+/// One, two or four sizes, read like the matching `AreaShort`.
+fn parse_area(value: &str) -> std::result::Result<AreaShort, String> {
+    let sizes = value
+        .split_whitespace()
+        .map(|size| size.parse::<u16>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid sizes {value:?}"))?;
+    match sizes[..] {
+        [all] => Ok(AreaShort::Uniform(all)),
+        [hor, ver] => Ok(AreaShort::HorVer(hor, ver)),
+        [top, right, bottom, left] => Ok(AreaShort::All(top, right, bottom, left)),
+        _ => Err(format!("expected 1, 2 or 4 sizes, got {value:?}")),
+    }
+}
+
+/// What an element is doing, for pseudo-classes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Hover,
+    Focus,
+    Pressed,
+}
+
+/// A compound selector such as `widget#save.primary:hover`. There are no
+/// combinators: every part applies to the element itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Selector {
+    /// The element type, see `Element::type_name`. `None` for `*` or when
+    /// the selector starts with an id, class or state.
+    pub element: Option<String>,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub states: Vec<State>,
+}
+
+impl Selector {
+    fn parse(source: &str) -> std::result::Result<Self, String> {
+        if source.is_empty() {
+            return Err("empty selector".into());
+        }
+        let mut selector = Selector::default();
+        let mut rest = source;
+        if let Some(after) = rest.strip_prefix('*') {
+            rest = after;
+        } else {
+            let name = ident(rest);
+            if !name.is_empty() {
+                selector.element = Some(name.into());
+                rest = &rest[name.len()..];
+            }
+        }
+        while let Some(prefix) = rest.chars().next() {
+            let name = ident(&rest[prefix.len_utf8()..]);
+            if name.is_empty() {
+                return Err(format!("unexpected {prefix:?} in selector {source:?}"));
+            }
+            match prefix {
+                '#' => selector.id = Some(name.into()),
+                '.' => selector.classes.push(name.into()),
+                ':' => selector.states.push(match name {
+                    "hover" => State::Hover,
+                    "focus" => State::Focus,
+                    "pressed" => State::Pressed,
+                    _ => return Err(format!("unknown pseudo-class :{name}")),
+                }),
+                _ => return Err(format!("unexpected {prefix:?} in selector {source:?}")),
+            }
+            rest = &rest[prefix.len_utf8() + name.len()..];
+        }
+        Ok(selector)
+    }
+
+    /// Ids, then classes and states, then types, compared in that order as
+    /// in CSS.
+    fn specificity(&self) -> (usize, usize, usize) {
+        (
+            self.id.iter().count(),
+            self.classes.len() + self.states.len(),
+            self.element.iter().count(),
+        )
+    }
+
+    fn matches(&self, el: &dyn Element, states: &[State]) -> bool {
+        self.element.as_deref().is_none_or(|e| e == el.type_name())
+            && self.id.as_deref().is_none_or(|id| el.id() == Some(id))
+            && self.classes.iter().all(|c| el.classes().contains(c))
+            && self.states.iter().all(|s| states.contains(s))
+    }
+}
+
+/// The name at the start of `source`.
+fn ident(source: &str) -> &str {
+    let end = source
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(source.len());
+    &source[..end]
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    /// The rule applies to elements matching any of these.
+    pub selectors: Vec<Selector>,
+    pub properties: Properties,
+}
+
+/// Rules in a CSS-like syntax that restyle elements by type, `#id`,
+/// `.class` and state:
+///
+/// ```css
+/// /* Comments like this. */
+/// block { border: rounded; border-color: blue; }
+/// widget.primary, #save { color: black; background: #88c0d0; padding: 0 1; }
+/// widget:hover { text-style: bold; }
+/// ```
+///
+/// Where several rules set the same property, the most specific selector
+/// wins, then the later rule.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+}
+
+impl Stylesheet {
+    pub fn parse(source: &str) -> Result<Self> {
+        let source = strip_comments(source);
+        let invalid = |offset: usize, message: String| {
+            let line = source[..offset].matches('\n').count() + 1;
+            Error::InvalidConfig(format!("stylesheet line {line}: {message}"))
+        };
+
+        let mut rules = vec![];
+        let mut offset = 0;
+        while let Some(open) = source[offset..].find('{').map(|i| offset + i) {
+            let close = source[open..]
+                .find('}')
+                .map(|i| open + i)
+                .ok_or_else(|| invalid(open, "`{` is never closed".into()))?;
+            let selectors = source[offset..open]
+                .split(',')
+                .map(|s| Selector::parse(s.trim()))
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|e| invalid(offset + leading_space(&source[offset..]), e))?;
+
+            let mut properties = Properties::default();
+            let mut at = open + 1;
+            for declaration in source[open + 1..close].split(';') {
+                if !declaration.trim().is_empty() {
+                    let parsed = match declaration.split_once(':') {
+                        Some((name, value)) => properties.set(name.trim(), value.trim()),
+                        None => Err(format!(
+                            "expected `name: value`, got {:?}",
+                            declaration.trim()
+                        )),
+                    };
+                    parsed.map_err(|e| invalid(at + leading_space(declaration), e))?;
+                }
+                at += declaration.len() + 1;
+            }
+            rules.push(Rule {
+                selectors,
+                properties,
+            });
+            offset = close + 1;
+        }
+        if !source[offset..].trim().is_empty() {
+            let at = offset + leading_space(&source[offset..]);
+            return Err(invalid(at, "expected `{` after the selector".into()));
+        }
+        Ok(Self { rules })
+    }
+
+    /// Reads and parses the file at `path`.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path.into())?)
+    }
+
+    /// What the stylesheet sets on `el` while it is in `states`.
+    pub fn properties(&self, el: &dyn Element, states: &[State]) -> Properties {
+        let mut matched: Vec<_> = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter_map(|(order, rule)| {
+                rule.selectors
+                    .iter()
+                    .filter(|s| s.matches(el, states))
+                    .map(Selector::specificity)
+                    .max()
+                    .map(|specificity| (specificity, order, rule.properties))
+            })
+            .collect();
+        matched.sort_by_key(|(specificity, order, _)| (*specificity, *order));
+        matched
+            .into_iter()
+            .fold(Properties::default(), |acc, (_, _, properties)| {
+                acc.patch(properties)
+            })
+    }
+
+    /// Gives every element below and including `el`, which sits at `path`,
+    /// its properties for its current state.
+    pub(crate) fn restyle(
+        &self,
+        el: &mut dyn Element,
+        path: &mut Vec<usize>,
+        focused: Option<&[usize]>,
+        hovered: Option<&[usize]>,
+        pressed: Option<&[usize]>,
+    ) {
+        let states: Vec<_> = [
+            (State::Hover, hovered),
+            (State::Focus, focused),
+            (State::Pressed, pressed),
+        ]
+        .into_iter()
+        .filter(|(_, at)| *at == Some(path.as_slice()))
+        .map(|(state, _)| state)
+        .collect();
+        let properties = self.properties(el, &states);
+        el.restyle(properties);
+        for i in 0..el.child_count() {
+            if let Some(child) = el.child_mut(i) {
+                path.push(i);
+                self.restyle(child, path, focused, hovered, pressed);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// Blanks out `/* ... */` comments, keeping newlines so that line numbers
+/// in errors still match the file.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("*/")
+            .map_or(rest.len(), |i| start + i + 2);
+        out.extend(
+            rest[start..end]
+                .chars()
+                .map(|c| if c == '\n' { '\n' } else { ' ' }),
+        );
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn leading_space(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+/// A stylesheet file that is read again whenever it changes on disk.
+#[derive(Debug)]
+pub struct StylesheetFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    stylesheet: Stylesheet,
+}
+
+impl StylesheetFile {
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let modified = fs::metadata(&path)?.modified().ok();
+        let stylesheet = Stylesheet::load(&path)?;
+        Ok(Self {
+            path,
+            modified,
+            stylesheet,
+        })
+    }
+
+    pub fn stylesheet(&self) -> &Stylesheet {
+        &self.stylesheet
+    }
+
+    /// Reads the file again if its modification time changed since the
+    /// last look, and returns whether the stylesheet changed. If the new
+    /// contents do not parse, the previous stylesheet stays and the error
+    /// is returned, once per change.
+    pub fn reload_if_changed(&mut self) -> Result<bool> {
+        let modified = fs::metadata(&self.path)?.modified().ok();
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        let stylesheet = Stylesheet::load(&self.path)?;
+        let changed = stylesheet != self.stylesheet;
+        self.stylesheet = stylesheet;
+        Ok(changed)
+    }
+}
+
+/// Writes `source` to `path` and backdates it to `secs` after the epoch, so
+/// tests control what `reload_if_changed` sees.
+#[cfg(test)]
+pub(crate) fn write_with_mtime(path: &std::path::Path, source: &str, secs: u64) {
+    fs::write(path, source).unwrap();
+    let file = fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::Widget;
+
+    #[test]
+    fn specific_selectors_win_and_states_apply() {
+        let sheet = Stylesheet::parse(
+            "
+            /* base */
+            widget { color: red; padding: 0 2; }
+            #ok, .big { color: blue; }
+            widget:hover { background: yellow; text-style: b u; }
+            * { color: green; border: rounded white; }
+            ",
+        )
+        .unwrap();
+        let plain = Widget::new("a");
+        let ok = Widget::new("b").id("ok");
+
+        let props = sheet.properties(&plain, &[]);
+        assert_eq!(props.style.fg, Some(Color::Red));
+        assert_eq!(props.padding, Some(AreaShort::HorVer(0, 2)));
+        assert_eq!(props.border, Some(BorderStyle::Rounded));
+        assert_eq!(props.border_color, Some(Color::White));
+        assert_eq!(sheet.properties(&ok, &[]).style.fg, Some(Color::Blue));
+
+        let hovered = sheet.properties(&plain, &[State::Hover]);
+        assert_eq!(hovered.style.bg, Some(Color::Yellow));
+        assert!(hovered
+            .style
+            .attributes
+            .has(crossterm::style::Attribute::Underlined));
+    }
+
+    #[test]
+    fn file_reloads_when_modified() {
+        let path = std::env::temp_dir().join(format!("bad-tui-{}.css", std::process::id()));
+        let write = |source, secs| write_with_mtime(&path, source, secs);
+        write("widget { color: red; }", 1);
+        let mut file = StylesheetFile::load(&path).unwrap();
+        assert!(!file.reload_if_changed().unwrap());
+
+        write("widget { color: blue; }", 2);
+        assert!(file.reload_if_changed().unwrap());
+        assert_eq!(
+            file.stylesheet().rules[0].properties.style.fg,
+            Some(Color::Blue)
+        );
+
+        // A broken edit is reported once and keeps the old rules.
+        write("widget { colour: red; }", 3);
+        assert!(file.reload_if_changed().is_err());
+        assert!(!file.reload_if_changed().unwrap());
+        assert_eq!(file.stylesheet().rules.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let error = Stylesheet::parse("widget {\n  color: red;\n  colour: red;\n}")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 3"), "{error}");
+        assert!(Stylesheet::parse("widget > block {}").is_err());
+        assert!(Stylesheet::parse("widget { color: red; ").is_err());
+        assert!(Stylesheet::parse(":active { }").is_err());
+    }
+}
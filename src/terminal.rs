@@ -0,0 +1,101 @@
+use crossterm::{
+    cursor,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, LeaveAlternateScreen},
+};
+use std::{
+    io::{self, stdout},
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+};
+
+/// Whether a backend switched to the alternate screen and has not left it.
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_alternate_screen(on: bool) {
+    ALTERNATE_SCREEN.store(on, Ordering::SeqCst);
+}
+
+/// Whether a `TerminalGuard` has set up the terminal and nothing restored
+/// it since. The panic hook leaves the terminal alone otherwise.
+static GUARD_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal into the state the UI needs and puts it back when
+/// dropped, including while unwinding from a panic. Creating one also
+/// installs `install_panic_hook`, so the panic message lands on a usable
+/// terminal instead of a raw, cursorless one.
+pub struct TerminalGuard {
+    active: bool,
+}
+
+impl TerminalGuard {
+    /// Enables raw mode and mouse capture and hides the cursor.
+    pub fn new() -> io::Result<Self> {
+        install_panic_hook();
+        enable_raw_mode()?;
+        let guard = Self { active: true };
+        GUARD_ACTIVE.store(true, Ordering::SeqCst);
+        execute!(stdout(), EnableMouseCapture, cursor::Hide)?;
+        Ok(guard)
+    }
+
+    /// Restores the terminal now instead of on drop, to see whether it
+    /// worked. Does nothing the second time.
+    pub fn restore(&mut self) -> io::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        restore()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+/// Undoes everything the UI may have done to the terminal: leaves the
+/// alternate screen, turns off mouse capture and raw mode, and shows the
+/// cursor in the user's own shape. Safe to call when none of it was done.
+pub fn restore() -> io::Result<()> {
+    GUARD_ACTIVE.store(false, Ordering::SeqCst);
+    let mut out = stdout();
+    // Leaving also restores a saved cursor position, which would throw off
+    // inline UIs, so only do it when there is something to leave.
+    let mut result = Ok(());
+    if ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+        result = execute!(out, LeaveAlternateScreen);
+    }
+    let result = result.and(execute!(
+        out,
+        DisableMouseCapture,
+        cursor::SetCursorStyle::DefaultUserShape,
+        cursor::Show
+    ));
+    // Leave raw mode even if the writes failed.
+    disable_raw_mode()?;
+    result
+}
+
+/// Makes panics while a `TerminalGuard` is active restore the terminal
+/// before the panic message is printed. Later panics, once the terminal is
+/// back to normal, are left alone. Installing it more than once has no
+/// further effect.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if GUARD_ACTIVE.load(Ordering::SeqCst) {
+                let _ = restore();
+            }
+            previous(info);
+        }));
+    });
+}
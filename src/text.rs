@@ -0,0 +1,489 @@
+use crate::{
+    error::{Error, Result},
+    style::{parse_attribute, parse_color, Style},
+    ui::display_width,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How text that is wider than its box is broken into lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Wrap {
+    /// Only break at newlines.
+    #[default]
+    None,
+    /// Break between words, and inside words that do not fit on a line.
+    Word,
+    /// Break anywhere.
+    Char,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Stretch the spaces between words to fill the line. The last line of
+    /// a paragraph stays left-aligned.
+    Justify,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// What to do with text that does not fit its box.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Cut it off.
+    #[default]
+    Clip,
+    /// Cut it off and end the last visible part with `…`.
+    Ellipsis,
+    /// Show a window of lines that the mouse wheel moves.
+    Scroll,
+}
+
+/// A run of text in one style.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub content: String,
+    pub style: Style,
+}
+
+impl Span {
+    pub fn raw<S: Into<String>>(content: S) -> Self {
+        Self::styled(content, Style::default())
+    }
+
+    pub fn styled<S: Into<String>>(content: S, style: Style) -> Self {
+        Self {
+            content: content.into(),
+            style,
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        display_width(&self.content)
+    }
+}
+
+impl From<&str> for Span {
+    fn from(content: &str) -> Self {
+        Span::raw(content)
+    }
+}
+
+impl From<String> for Span {
+    fn from(content: String) -> Self {
+        Span::raw(content)
+    }
+}
+
+/// One line of text made of differently styled spans.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Line {
+    pub spans: Vec<Span>,
+}
+
+impl Line {
+    pub fn new(spans: Vec<Span>) -> Self {
+        Self { spans }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.spans
+            .iter()
+            .fold(0, |acc: u16, span| acc.saturating_add(span.width()))
+    }
+
+    /// Every grapheme with the style of its span.
+    fn graphemes(&self) -> impl Iterator<Item = (&str, Style)> {
+        self.spans
+            .iter()
+            .flat_map(|span| span.content.graphemes(true).map(|g| (g, span.style)))
+    }
+
+    /// Appends `text`, extending the last span if it has the same style.
+    fn push_str(&mut self, text: &str, style: Style) {
+        match self.spans.last_mut() {
+            Some(last) if last.style == style => last.content.push_str(text),
+            _ => self.spans.push(Span::styled(text, style)),
+        }
+    }
+}
+
+impl From<&str> for Line {
+    fn from(content: &str) -> Self {
+        Line::new(vec![content.into()])
+    }
+}
+
+impl From<String> for Line {
+    fn from(content: String) -> Self {
+        Line::new(vec![content.into()])
+    }
+}
+
+impl From<Span> for Line {
+    fn from(span: Span) -> Self {
+        Line::new(vec![span])
+    }
+}
+
+impl From<Vec<Span>> for Line {
+    fn from(spans: Vec<Span>) -> Self {
+        Line::new(spans)
+    }
+}
+
+/// Lines of styled text, one per paragraph.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Text {
+    pub lines: Vec<Line>,
+}
+
+impl Text {
+    /// Parses inline markup. `[b red]error[/]` makes "error" bold and red:
+    /// a tag holds attributes (see `parse_attribute`), colors (see
+    /// `parse_color`) and `on <color>` for the background, and `[/]` ends
+    /// the latest tag. Tags nest, and `[[` is a literal `[`.
+    pub fn markup(source: &str) -> Result<Self> {
+        let mut lines = vec![Line::default()];
+        let mut styles = vec![Style::default()];
+        let mut rest = source;
+        while let Some(i) = rest.find(['[', '\n']) {
+            let style = *styles.last().unwrap_or(&Style::default());
+            let line = lines.last_mut().expect("there is always a line");
+            if i > 0 {
+                line.push_str(&rest[..i], style);
+            }
+            rest = &rest[i..];
+            if let Some(after) = rest.strip_prefix('\n') {
+                lines.push(Line::default());
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("[[") {
+                line.push_str("[", style);
+                rest = after;
+            } else {
+                let end = rest.find(']').ok_or_else(|| {
+                    Error::InvalidConfig(format!("unclosed markup tag in {source:?}"))
+                })?;
+                let tag = &rest[1..end];
+                if tag == "/" {
+                    if styles.len() == 1 {
+                        return Err(Error::InvalidConfig(format!(
+                            "[/] without an open tag in {source:?}"
+                        )));
+                    }
+                    styles.pop();
+                } else {
+                    styles.push(style.patch(parse_tag(tag)?));
+                }
+                rest = &rest[end + 1..];
+            }
+        }
+        let style = *styles.last().unwrap_or(&Style::default());
+        if !rest.is_empty() {
+            lines
+                .last_mut()
+                .expect("there is always a line")
+                .push_str(rest, style);
+        }
+        Ok(Self { lines })
+    }
+
+    pub fn width(&self) -> u16 {
+        self.lines.iter().map(Line::width).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> u16 {
+        self.lines.len() as u16
+    }
+}
+
+/// The style of a markup tag such as `b red on blue`.
+fn parse_tag(tag: &str) -> Result<Style> {
+    let mut style = Style::default();
+    let mut words = tag.split_whitespace();
+    while let Some(word) = words.next() {
+        let invalid = |word: &str| Error::InvalidConfig(format!("unknown markup style {word:?}"));
+        if word == "on" {
+            let color = words.next().unwrap_or_default();
+            style = style.bg(parse_color(color).ok_or_else(|| invalid(color))?);
+        } else if let Some(attribute) = parse_attribute(word) {
+            style = style.attribute(attribute);
+        } else {
+            style = style.fg(parse_color(word).ok_or_else(|| invalid(word))?);
+        }
+    }
+    Ok(style)
+}
+
+impl From<&str> for Text {
+    fn from(content: &str) -> Self {
+        Self {
+            lines: content.split('\n').map(Line::from).collect(),
+        }
+    }
+}
+
+impl From<String> for Text {
+    fn from(content: String) -> Self {
+        content.as_str().into()
+    }
+}
+
+impl From<Span> for Text {
+    fn from(span: Span) -> Self {
+        Line::from(span).into()
+    }
+}
+
+impl From<Line> for Text {
+    fn from(line: Line) -> Self {
+        Self { lines: vec![line] }
+    }
+}
+
+impl From<Vec<Line>> for Text {
+    fn from(lines: Vec<Line>) -> Self {
+        Self { lines }
+    }
+}
+
+/// One line of wrapped text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrappedLine {
+    pub line: Line,
+    pub width: u16,
+    /// Whether a newline or the end of the text comes after this line, as
+    /// opposed to a wrap.
+    pub ends_paragraph: bool,
+}
+
+impl WrappedLine {
+    fn new(line: Line) -> Self {
+        Self {
+            width: line.width(),
+            line,
+            ends_paragraph: false,
+        }
+    }
+}
+
+/// Breaks `text` into lines no wider than `width`, unless `wrap` is
+/// `Wrap::None`.
+pub fn wrap(text: &Text, width: u16, wrap: Wrap) -> Vec<WrappedLine> {
+    let mut lines = vec![];
+    for paragraph in &text.lines {
+        let start = lines.len();
+        match wrap {
+            Wrap::None => lines.push(WrappedLine::new(paragraph.clone())),
+            Wrap::Char => wrap_chars(paragraph.graphemes(), width, &mut lines),
+            Wrap::Word => wrap_words(paragraph, width, &mut lines),
+        }
+        if lines.len() == start {
+            lines.push(WrappedLine::new(Line::default()));
+        }
+        if let Some(last) = lines.last_mut() {
+            last.ends_paragraph = true;
+        }
+    }
+    lines
+}
+
+fn wrap_chars<'t>(
+    graphemes: impl IntoIterator<Item = (&'t str, Style)>,
+    width: u16,
+    lines: &mut Vec<WrappedLine>,
+) {
+    let mut line = Line::default();
+    let mut line_width = 0;
+    for (grapheme, style) in graphemes {
+        let w = display_width(grapheme);
+        if line_width + w > width && !line.spans.is_empty() {
+            lines.push(WrappedLine::new(std::mem::take(&mut line)));
+            line_width = 0;
+        }
+        line.push_str(grapheme, style);
+        line_width += w;
+    }
+    if !line.spans.is_empty() {
+        lines.push(WrappedLine::new(line));
+    }
+}
+
+fn wrap_words(paragraph: &Line, width: u16, lines: &mut Vec<WrappedLine>) {
+    // Split into words, remembering the style of the space before each.
+    let mut words: Vec<(Style, Vec<(&str, Style)>)> = vec![];
+    let mut space = Style::default();
+    let mut in_word = false;
+    for (grapheme, style) in paragraph.graphemes() {
+        if grapheme.trim().is_empty() {
+            if in_word {
+                space = style;
+            }
+            in_word = false;
+        } else {
+            if !in_word {
+                words.push((space, vec![]));
+            }
+            in_word = true;
+            if let Some((_, word)) = words.last_mut() {
+                word.push((grapheme, style));
+            }
+        }
+    }
+
+    let mut line = Line::default();
+    let mut line_width = 0;
+    for (space, word) in words {
+        let w = word
+            .iter()
+            .fold(0u16, |acc, (g, _)| acc.saturating_add(display_width(g)));
+        if !line.spans.is_empty() && line_width + 1 + w <= width {
+            line.push_str(" ", space);
+            word.iter().for_each(|(g, style)| line.push_str(g, *style));
+            line_width += 1 + w;
+            continue;
+        }
+        if !line.spans.is_empty() {
+            lines.push(WrappedLine::new(std::mem::take(&mut line)));
+        }
+        if w > width {
+            // Too long for any line: break it, and keep its tail open for
+            // the words after it.
+            wrap_chars(word, width, lines);
+            if let Some(tail) = lines.pop() {
+                line_width = tail.width;
+                line = tail.line;
+            }
+        } else {
+            word.iter().for_each(|(g, style)| line.push_str(g, *style));
+            line_width = w;
+        }
+    }
+    if !line.spans.is_empty() {
+        lines.push(WrappedLine::new(line));
+    }
+}
+
+/// Where `line` starts inside `width` columns, and its text with any
+/// justification spaces added.
+pub fn align(line: &WrappedLine, width: u16, align: Align) -> (u16, Line) {
+    let free = width.saturating_sub(line.width);
+    match align {
+        Align::Left => (0, line.line.clone()),
+        Align::Center => (free / 2, line.line.clone()),
+        Align::Right => (free, line.line.clone()),
+        Align::Justify if line.ends_paragraph || free == 0 => (0, line.line.clone()),
+        Align::Justify => (0, justify(&line.line, free)),
+    }
+}
+
+/// Spreads `extra` more spaces over the gaps between the words of `line`,
+/// leftmost gaps first.
+fn justify(line: &Line, extra: u16) -> Line {
+    let gaps = line.graphemes().filter(|(g, _)| *g == " ").count();
+    if gaps == 0 {
+        return line.clone();
+    }
+    let (each, rest) = (extra as usize / gaps, extra as usize % gaps);
+    let mut out = Line::default();
+    let mut gap = 0;
+    for (grapheme, style) in line.graphemes() {
+        out.push_str(grapheme, style);
+        if grapheme == " " {
+            gap += 1;
+            let spaces = each + usize::from(gap <= rest);
+            out.push_str(&" ".repeat(spaces), style);
+        }
+    }
+    out
+}
+
+/// Cuts `line` down to `width` columns, ending it with `…` if anything was
+/// cut or `always` is set.
+pub fn ellipsize(line: &Line, width: u16, always: bool) -> Line {
+    if width == 0 || (!always && line.width() <= width) {
+        return line.clone();
+    }
+    let mut out = Line::default();
+    let mut used = 0;
+    let mut style = Style::default();
+    for (grapheme, grapheme_style) in line.graphemes() {
+        let w = display_width(grapheme);
+        if used + w > width - 1 {
+            break;
+        }
+        out.push_str(grapheme, grapheme_style);
+        style = grapheme_style;
+        used += w;
+    }
+    out.push_str("…", style);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::{Attribute, Color};
+
+    fn texts(lines: &[WrappedLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|l| l.line.spans.iter().map(|s| s.content.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn word_wrap_breaks_between_words_and_inside_long_ones() {
+        let lines = wrap(&"the quick brown\nfox abcdefghij".into(), 6, Wrap::Word);
+        assert_eq!(
+            texts(&lines),
+            vec!["the", "quick", "brown", "fox", "abcdef", "ghij"]
+        );
+        assert!(lines[2].ends_paragraph);
+        assert!(!lines[4].ends_paragraph);
+        assert_eq!(
+            texts(&wrap(&"界界界".into(), 5, Wrap::Char)),
+            vec!["界界", "界"]
+        );
+    }
+
+    #[test]
+    fn justify_spreads_spaces_but_not_on_the_last_line() {
+        let lines = wrap(&"a b c d".into(), 6, Wrap::Word);
+        assert_eq!(align(&lines[0], 8, Align::Justify).1, "a   b  c".into());
+        assert_eq!(align(&lines[1], 8, Align::Justify), (0, "d".into()));
+        assert_eq!(align(&lines[1], 8, Align::Right), (7, "d".into()));
+        assert_eq!(ellipsize(&"abcdef".into(), 4, false), "abc…".into());
+    }
+
+    #[test]
+    fn markup_styles_spans() {
+        let text = Text::markup("[b red]error[/]: [d]bad [on blue]thing[/][/]\n[[x]").unwrap();
+        let bold_red = Style::new().fg(Color::Red).attribute(Attribute::Bold);
+        let dim = Style::new().attribute(Attribute::Dim);
+        assert_eq!(
+            text.lines,
+            vec![
+                Line::new(vec![
+                    Span::styled("error", bold_red),
+                    Span::raw(": "),
+                    Span::styled("bad ", dim),
+                    Span::styled("thing", dim.bg(Color::Blue)),
+                ]),
+                Line::from("[x]"),
+            ]
+        );
+        assert!(Text::markup("[nope]x").is_err());
+        assert!(Text::markup("x[/]").is_err());
+        assert!(Text::markup("[b").is_err());
+    }
+}
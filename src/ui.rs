@@ -1,531 +1,3437 @@
+use crate::{
+    border::Border,
+    color::ColorSupport,
+    error::{Error, Result},
+    event::{
+        dispatch, hit_test, EventContext, Handlers, MouseState, Phase, ScrollCallback, UiEvent,
+    },
+    focus::{ElementPath, FocusDirection, FocusManager},
+    style::{Style, Theme},
+    stylesheet::{Properties, Stylesheet},
+    text::{self, Align, Overflow, Text, VerticalAlign, Wrap, WrappedLine},
+};
 use crossterm::{
     cursor,
-    event::{Event, MouseButton},
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind},
     queue,
-    style::{Color, Print, SetBackgroundColor, SetForegroundColor},
-    terminal::size,
+    style::{
+        Attribute, Attributes, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
+        SetUnderlineColor,
+    },
+    terminal::{self, size, ClearType},
 };
 use std::{
-    cell::{Ref, RefCell},
+    borrow::Cow,
     cmp,
-    io::{Stdout, Write},
+    io::{self, Write},
+    mem,
     ops::{Index, IndexMut},
-    rc::Rc,
-    slice::SliceIndex,
+    time::{Duration, Instant},
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-const BG_RESET: SetBackgroundColor = SetBackgroundColor(Color::Reset);
-const FG_RESET: SetForegroundColor = SetForegroundColor(Color::Reset);
-
-pub struct UI<'a> {
-    // pub elements: Vec<Block<'a>>,
-    pub stdout: &'a mut Stdout,
-    pub root: Block<'a>,
-    // pub state: &mut State,
-    // pub pos: Option<(u8, u8)>,
+/// Everything the UI needs from a terminal. Implement this to render
+/// somewhere other than a real terminal.
+pub trait Backend {
+    fn move_to(&mut self, col: u16, row: u16) -> io::Result<()>;
+    fn print(&mut self, text: &str) -> io::Result<()>;
+    fn set_foreground(&mut self, color: Color) -> io::Result<()>;
+    fn set_background(&mut self, color: Color) -> io::Result<()>;
+    fn set_attribute(&mut self, attribute: Attribute) -> io::Result<()>;
+    fn set_underline_color(&mut self, color: Color) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+    /// WIDTH, HEIGHT
+    fn size(&self) -> io::Result<(u16, u16)>;
+    fn clear(&mut self) -> io::Result<()>;
+    /// COLUMN, ROW
+    fn cursor_position(&mut self) -> io::Result<(u16, u16)>;
+    fn enter_alternate_screen(&mut self) -> io::Result<()>;
+    fn leave_alternate_screen(&mut self) -> io::Result<()>;
 }
 
-#[derive(Clone, Debug)]
-struct Pixel {
-    char: char,
-    color: Color,
-    changed: bool,
-}
+impl<B: Backend + ?Sized> Backend for &mut B {
+    fn move_to(&mut self, col: u16, row: u16) -> io::Result<()> {
+        (**self).move_to(col, row)
+    }
 
-impl Default for Pixel {
-    fn default() -> Self {
-        Self {
-            char: ' ',
-            color: Color::White,
-            changed: false,
-        }
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        (**self).print(text)
     }
-}
 
-#[derive(Clone, Debug)]
-pub struct Context {
-    pub max: (u16, u16),
-    pub click_pos: Option<(u16, u16)>,
-    // pub offset: u8,
-    pub bg_color: Color,
-    virtual_display: VirtualDisplay,
-}
+    fn set_foreground(&mut self, color: Color) -> io::Result<()> {
+        (**self).set_foreground(color)
+    }
 
-#[derive(Clone, Debug)]
-struct VirtualDisplay(Vec<VirtualDisplayRow>);
+    fn set_background(&mut self, color: Color) -> io::Result<()> {
+        (**self).set_background(color)
+    }
 
-#[derive(Clone, Debug)]
-struct VirtualDisplayRow(Vec<Pixel>);
+    fn set_attribute(&mut self, attribute: Attribute) -> io::Result<()> {
+        (**self).set_attribute(attribute)
+    }
 
-impl Index<u16> for VirtualDisplay {
-    type Output = VirtualDisplayRow;
+    fn set_underline_color(&mut self, color: Color) -> io::Result<()> {
+        (**self).set_underline_color(color)
+    }
 
-    fn index(&self, index: u16) -> &Self::Output {
-        &self.0[index as usize]
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
     }
-}
 
-impl IndexMut<u16> for VirtualDisplay {
-    fn index_mut(&mut self, index: u16) -> &mut Self::Output {
-        &mut self.0[index as usize]
+    fn size(&self) -> io::Result<(u16, u16)> {
+        (**self).size()
     }
-}
 
-impl Index<u16> for VirtualDisplayRow {
-    type Output = Pixel;
+    fn clear(&mut self) -> io::Result<()> {
+        (**self).clear()
+    }
 
-    fn index(&self, index: u16) -> &Self::Output {
-        &self.0[index as usize]
+    fn cursor_position(&mut self) -> io::Result<(u16, u16)> {
+        (**self).cursor_position()
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        (**self).enter_alternate_screen()
+    }
+
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        (**self).leave_alternate_screen()
     }
 }
 
-impl IndexMut<u16> for VirtualDisplayRow {
-    fn index_mut(&mut self, index: u16) -> &mut Self::Output {
-        &mut self.0[index as usize]
+/// Backend that queues crossterm commands onto any writer, usually `Stdout`.
+pub struct CrosstermBackend<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CrosstermBackend<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.writer
     }
 }
 
-impl Context {
-    pub fn new() -> Self {
-        let (width, height) = size().unwrap();
-        Context {
-            bg_color: Color::Red,
-            click_pos: None,
-            max: (width, height),
-            virtual_display: VirtualDisplay(vec![
-                VirtualDisplayRow(vec![
-                    Pixel::default();
-                    width.into()
-                ]);
-                height.into()
-            ]),
-        }
+impl<W: Write> Backend for CrosstermBackend<W> {
+    fn move_to(&mut self, col: u16, row: u16) -> io::Result<()> {
+        queue!(self.writer, cursor::MoveTo(col, row))
     }
 
-    pub fn set_size(&mut self, new_size: (u16, u16)) {
-        self.max = new_size;
-        self.virtual_display = VirtualDisplay(vec![
-            VirtualDisplayRow(vec![
-                Pixel::default();
-                new_size.0.into()
-            ]);
-            new_size.1.into()
-        ]);
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        queue!(self.writer, Print(text))
     }
 
-    pub fn process(&mut self, event: &Event) {
-        self.click_pos = match event {
-            Event::Mouse(event) => match event.kind {
-                crossterm::event::MouseEventKind::Down(MouseButton::Left) => {
-                    Some((event.column, event.row))
-                }
-                crossterm::event::MouseEventKind::Drag(MouseButton::Left) => {
-                    Some((event.column, event.row))
-                }
-                _ => None,
-            },
-            _ => None,
-        };
+    fn set_foreground(&mut self, color: Color) -> io::Result<()> {
+        queue!(self.writer, SetForegroundColor(color))
     }
-}
 
-impl<'a> UI<'a> {
-    pub fn render(&mut self, ctx: &mut Context) {
-        self.root.calc_self();
-        self.root.render(self.stdout, ctx);
-        queue!(
-            self.stdout,
-            SetBackgroundColor(Color::Reset),
-            SetForegroundColor(Color::Reset)
-        )
-        .unwrap();
+    fn set_background(&mut self, color: Color) -> io::Result<()> {
+        queue!(self.writer, SetBackgroundColor(color))
+    }
 
-        for (col_pos, column) in ctx.virtual_display.0.iter_mut().enumerate() {
-            for (row_pos, px) in column.0.iter_mut().enumerate() {
-                if !px.changed {
-                    continue;
-                }
-                queue!(
-                    self.stdout,
-                    cursor::MoveTo(col_pos as u16, row_pos as u16),
-                    SetForegroundColor(px.color),
-                    crossterm::style::Print(px.char)
-                )
-                .unwrap();
-                px.changed = false;
-            }
-        }
+    fn set_attribute(&mut self, attribute: Attribute) -> io::Result<()> {
+        queue!(self.writer, SetAttribute(attribute))
     }
 
-    pub fn process(&mut self, ctx: &Context) {
-        self.root.process(ctx.click_pos);
+    fn set_underline_color(&mut self, color: Color) -> io::Result<()> {
+        queue!(self.writer, SetUnderlineColor(color))
     }
-}
 
-pub enum Element<'a> {
-    Block(Block<'a>),
-    Widget(Widget),
-}
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
 
-///! ORDER IS: COLUMN, ROW
-///! WIDTH, HEIGHT
-pub struct Block<'a> {
-    contents: Vec<RefCell<Element<'a>>>,
-    // parent: Option<Rc<Block<'a>>>,
-    pub pos: (u16, u16),
-    pub size: (u16, u16),
-    inner_pos: (u16, u16),
-    available_margin: (u16, u16),
-    pub direction: Direction,
+    fn size(&self) -> io::Result<(u16, u16)> {
+        size()
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        queue!(self.writer, terminal::Clear(ClearType::All))
+    }
+
+    fn cursor_position(&mut self) -> io::Result<(u16, u16)> {
+        self.writer.flush()?;
+        cursor::position()
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        queue!(self.writer, terminal::EnterAlternateScreen)?;
+        crate::terminal::set_alternate_screen(true);
+        Ok(())
+    }
+
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        queue!(self.writer, terminal::LeaveAlternateScreen)?;
+        crate::terminal::set_alternate_screen(false);
+        Ok(())
+    }
 }
 
-pub enum Direction {
-    Vertical,
-    Horizontal,
+/// Headless backend that draws into an in-memory grid, for tests.
+pub struct TestBackend {
+    display: VirtualDisplay,
+    size: (u16, u16),
+    cursor: (u16, u16),
+    pen: Pen,
+    /// The main screen and its cursor while the alternate screen is shown.
+    main_screen: Option<(VirtualDisplay, (u16, u16))>,
 }
 
-impl<'a> Block<'a> {
-    pub fn render(&mut self, stdout: &mut Stdout, ctx: &mut Context) {
-        // let (max_width, max_height) = max;
-        // Print the top border
-        // queue!(stdout,);
-        // queue!(
-        //     stdout,
-        //     cursor::MoveTo(self.pos.0, self.pos.1),
-        //     Print("#".repeat((self.size.0).into()))
-        // )
-        // .unwrap();
-        for row in self.pos.0..self.pos.0 + self.size.0 {
-            for col in self.pos.1..self.pos.1 + self.size.1 {
-                ctx.virtual_display[row][col] = Pixel {
-                    char: '#',
-                    color: Color::White,
-                    changed: true,
-                };
-            }
+impl TestBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            display: VirtualDisplay::new((width, height)),
+            size: (width, height),
+            cursor: (0, 0),
+            pen: Pen::default(),
+            main_screen: None,
         }
+    }
 
-        for row in self.pos.0 + 1..self.pos.0 + self.size.0 - 1 {
-            for col in self.pos.1 + 1..self.pos.1 + self.size.1 - 1 {
-                ctx.virtual_display[row][col] = Pixel {
-                    char: ' ',
-                    color: Color::White,
-                    changed: true,
-                };
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.size = (width, height);
+        self.display = VirtualDisplay::new(self.size);
+    }
+
+    /// The grid as rows of styled cells.
+    pub fn cells(&self) -> Vec<Vec<Pixel>> {
+        self.display.0.iter().map(|row| row.0.clone()).collect()
+    }
+
+    /// The grid as plain text, one line per row.
+    pub fn to_plain_string(&self) -> String {
+        self.display
+            .0
+            .iter()
+            .map(|row| {
+                row.0
+                    .iter()
+                    .map(|px| px.symbol.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Compares the grid to `expected` line by line, ignoring a leading
+    /// newline, trailing whitespace and trailing empty lines. Returns a
+    /// readable diff on mismatch.
+    pub fn frame_diff(&self, expected: &str) -> Option<String> {
+        let expected = expected.strip_prefix('\n').unwrap_or(expected);
+        let actual = self.to_plain_string();
+        let normalize = |s: &str| {
+            let mut lines: Vec<String> = s.lines().map(|l| l.trim_end().to_string()).collect();
+            while lines.last().is_some_and(|l| l.is_empty()) {
+                lines.pop();
             }
+            lines
+        };
+        let (expected, actual) = (normalize(expected), normalize(&actual));
+        if expected == actual {
+            return None;
         }
-        // Print the bottom border
-        // queue!(
-        //     stdout,
-        //     cursor::MoveTo(self.pos.0, self.pos.1 + self.size.1),
-        //     Print("#".repeat((self.size.0).into()))
-        // )
-        // .unwrap();
 
-        // queue!(stdout, cursor::MoveTo(self.pos.0 + 1, self.pos.1 + 1)).unwrap();
-        let max = (
-            self.size.0.checked_sub(2).unwrap_or(0),
-            self.size.1.checked_sub(3).unwrap_or(0),
-        );
-        for el in self.contents.iter_mut() {
-            match el.get_mut() {
-                Element::Block(block) => {
-                    block.render(stdout, ctx);
-                }
-                Element::Widget(widget) => {
-                    widget.render(stdout);
+        let mut diff = String::new();
+        for i in 0..cmp::max(expected.len(), actual.len()) {
+            match (expected.get(i), actual.get(i)) {
+                (Some(e), Some(a)) if e == a => diff.push_str(&format!("  {:>3} |{}|\n", i, a)),
+                (e, a) => {
+                    if let Some(e) = e {
+                        diff.push_str(&format!("- {:>3} |{}|\n", i, e));
+                    }
+                    if let Some(a) = a {
+                        diff.push_str(&format!("+ {:>3} |{}|\n", i, a));
+                    }
                 }
             }
         }
+        Some(diff)
     }
 
-    pub fn calc_self(&mut self) {
-        self.size = (4, 4);
-        for el in self.contents.iter_mut() {
-            match el.get_mut() {
-                Element::Block(block) => {
-                    block.calc_self();
-                    self.size.0 += block.size.0;
-                    self.size.1 += block.size.1;
-                }
-                Element::Widget(widget) => {
-                    widget.calc_self();
-                    self.size.0 += widget.size.0;
-                    self.size.1 += widget.size.1;
-                }
-            }
-        }
+    /// Whether the alternate screen is being shown.
+    pub fn is_alternate_screen(&self) -> bool {
+        self.main_screen.is_some()
     }
 
-    pub fn calc_parent(&mut self, parent_pos: (u16, u16)) {
-        self.pos.0 += parent_pos.0;
-        self.pos.1 += parent_pos.1;
-        println!("{:?}", self.inner_pos);
-        self.calc_self();
+    /// Moves the cursor down a line, scrolling the grid at the bottom.
+    fn line_feed(&mut self) {
+        if self.cursor.1 + 1 < self.size.1 {
+            self.cursor.1 += 1;
+        } else if !self.display.0.is_empty() {
+            self.display.0.remove(0);
+            self.display.0.push(VirtualDisplayRow(vec![
+                Pixel::default();
+                self.size.0 as usize
+            ]));
+        }
+    }
 
-        for el in self.contents.iter_mut() {
-            match el.get_mut() {
-                Element::Block(block) => {
-                    block.calc_parent(self.inner_pos);
-                }
-                // TODO
-                Element::Widget(_) => {
-                    // widget.process(click_pos);
-                }
-            }
+    /// Panics with a line diff (`-` expected, `+` actual) if the grid does
+    /// not match `expected`.
+    #[track_caller]
+    pub fn assert_frame(&self, expected: &str) {
+        if let Some(diff) = self.frame_diff(expected) {
+            panic!("frame does not match expected:\n{}", diff);
         }
     }
+}
+
+impl Backend for TestBackend {
+    fn move_to(&mut self, col: u16, row: u16) -> io::Result<()> {
+        self.cursor = (col, row);
+        Ok(())
+    }
 
-    pub fn process(&mut self, click_pos: Option<(u16, u16)>) {
-        for el in self.contents.iter_mut() {
-            match el.get_mut() {
-                Element::Block(block) => {
-                    block.process(click_pos);
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        for grapheme in text.graphemes(true) {
+            if grapheme.contains(['\r', '\n']) {
+                if grapheme.contains('\r') {
+                    self.cursor.0 = 0;
                 }
-                Element::Widget(widget) => {
-                    widget.process(click_pos);
+                if grapheme.contains('\n') {
+                    self.line_feed();
                 }
+                continue;
+            }
+            let px = Pixel {
+                symbol: grapheme.to_string(),
+                color: self.pen.color,
+                background: self.pen.background,
+                attributes: self.pen.attributes,
+                underline_color: self.pen.underline_color,
+            };
+            let width = px.width();
+            if width == 0 {
+                continue;
             }
+            self.display.put(self.cursor.0, self.cursor.1, px);
+            self.cursor.0 = self.cursor.0.saturating_add(width);
         }
+        Ok(())
     }
 
-    pub fn new(pos: (u16, u16)) -> Self {
-        Self {
-            // parent: None,
-            pos,
-            size: (3, 3),
-            inner_pos: (2, 2),
-            available_margin: (0, 0),
-            contents: vec![],
-            direction: Direction::Horizontal,
+    fn set_foreground(&mut self, color: Color) -> io::Result<()> {
+        self.pen.color = color;
+        Ok(())
+    }
+
+    fn set_background(&mut self, color: Color) -> io::Result<()> {
+        self.pen.background = color;
+        Ok(())
+    }
+
+    fn set_attribute(&mut self, attribute: Attribute) -> io::Result<()> {
+        if attribute == Attribute::Reset {
+            self.pen = Pen::default();
+            return Ok(());
+        }
+        match ATTRIBUTE_GROUPS.iter().find(|(off, _)| *off == attribute) {
+            Some((_, members)) => members.iter().for_each(|a| self.pen.attributes.unset(*a)),
+            None => self.pen.attributes.set(attribute),
         }
+        Ok(())
     }
 
-    pub fn push(&mut self, w: Element<'a>) -> Ref<Element<'a>> {
-        // if !self.widgets.is_empty() {
-        //     self.offset += self.pad as u8;
-        // }
-        let mut r = RefCell::new(w);
-        match r.get_mut() {
-            Element::Block(ref mut block) => {
-                block.pos = (block.pos.0 + self.pos.0, block.pos.1 + self.pos.1);
-                block.calc_parent(self.inner_pos);
-                // match self.direction {
-                //     Direction::Horizontal => self.inner_pos.0 += block.size.0,
-                //     Direction::Vertical => self.inner_pos.1 += block.size.1,
-                // }
-            }
-            Element::Widget(ref mut widget) => {
-                let adjusted = (
-                    cmp::min(self.available_margin.0, widget.margin.left),
-                    cmp::min(self.available_margin.1, widget.margin.top),
-                );
-                widget.pos = (
-                    widget.pos.0 + self.pos.0 + self.inner_pos.0 - adjusted.0,
-                    widget.pos.1 + self.pos.1 + self.inner_pos.1 - adjusted.1,
-                );
-                widget.calc_self();
-                match self.direction {
-                    Direction::Horizontal => {
-                        self.inner_pos.0 += widget.size.0 - adjusted.0;
-                        self.available_margin.0 = widget.margin.left;
-                    }
-                    Direction::Vertical => {
-                        self.inner_pos.1 += widget.size.1 - adjusted.1;
-                        self.available_margin.1 = widget.margin.top;
-                    }
-                }
-            }
+    fn set_underline_color(&mut self, color: Color) -> io::Result<()> {
+        self.pen.underline_color = match color {
+            Color::Reset => None,
+            color => Some(color),
         };
-        // w.process(self.pos, ui.click_pos);
-        self.contents.push(r);
-        self.contents[self.contents.len() - 1].borrow()
+        Ok(())
     }
-}
 
-pub struct Area {
-    pub top: u16,
-    pub right: u16,
-    pub bottom: u16,
-    pub left: u16,
-    pub symbol: char,
-    pub color: Color,
-    pub background: Color,
-}
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 
-pub enum AreaShort {
-    Uniform(u16),
-    HorVer(u16, u16),
-    All(u16, u16, u16, u16),
-}
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok(self.size)
+    }
 
-impl Area {
-    pub fn set(mut self, short: AreaShort) -> Self {
-        (self.top, self.right, self.bottom, self.left) = match short {
-            AreaShort::Uniform(uni) => (uni, uni, uni, uni),
-            AreaShort::HorVer(hor, ver) => (hor, ver, hor, ver),
-            AreaShort::All(t, r, b, l) => (t, r, b, l),
-        };
-        self
+    fn clear(&mut self) -> io::Result<()> {
+        self.display = VirtualDisplay::new(self.size);
+        Ok(())
     }
 
-    pub fn symbol(mut self, symbol: char) -> Self {
-        self.symbol = symbol;
-        self
+    fn cursor_position(&mut self) -> io::Result<(u16, u16)> {
+        Ok(self.cursor)
     }
 
-    pub fn color(mut self, color: Color) -> Self {
-        self.color = color;
-        self
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        if self.main_screen.is_none() {
+            let main = mem::replace(&mut self.display, VirtualDisplay::new(self.size));
+            self.main_screen = Some((main, self.cursor));
+        }
+        Ok(())
     }
-}
 
-impl Default for Area {
-    fn default() -> Self {
-        Self {
-            top: 1,
-            right: 1,
-            bottom: 1,
-            left: 1,
-            symbol: ' ',
-            color: Color::White,
-            background: Color::Black,
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        if let Some((main, cursor)) = self.main_screen.take() {
+            self.display = main;
+            self.cursor = cursor;
         }
+        Ok(())
     }
 }
 
-pub struct Widget {
-    pub text: String,
-    // pub color: Color,
-    // pub bg: Option<Color>,
-    pub padding: Area,
-    pub margin: Area,
-    // Including margin & padding!
-    pub size: (u16, u16),
-    pub pos: (u16, u16),
-    clicked: bool,
+pub struct UI<'a, B: Backend> {
+    // pub elements: Vec<Block<'a>>,
+    pub backend: B,
+    pub root: Block<'a>,
+    // pub state: &mut State,
+    // pub pos: Option<(u8, u8)>,
+    screen: ScreenMode,
+    keep_final_frame: Option<bool>,
 }
 
-impl Widget {
-    pub fn new<S: Into<String>>(text: S) -> Self {
-        Widget {
-            text: text.into(),
-            // color,
-            // bg: None,
-            pos: (0, 0),
-            clicked: false,
-            padding: Area::default().symbol('$'),
-            size: (3, 3),
-            margin: Area::default().symbol('#'),
+/// Where on the terminal the UI draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenMode {
+    /// The whole terminal, on the alternate screen.
+    Fullscreen,
+    /// This many lines starting at the cursor's line, on the main screen.
+    /// Scrollback above is left alone.
+    Inline(u16),
+    /// Only this rectangle of the main screen.
+    Fixed(Rect),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pixel {
+    /// One grapheme cluster. Empty for the cell covered by the right half
+    /// of a double-width grapheme to its left.
+    pub symbol: String,
+    pub color: Color,
+    pub background: Color,
+    pub attributes: Attributes,
+    /// `None` follows the foreground color.
+    pub underline_color: Option<Color>,
+}
+
+impl Pixel {
+    pub fn new<S: Into<String>>(symbol: S, color: Color, background: Color) -> Self {
+        Self {
+            symbol: symbol.into(),
+            color,
+            background,
+            attributes: Attributes::default(),
+            underline_color: None,
         }
     }
 
-    pub fn padding<F>(mut self, f: F) -> Self
-    where
-        F: FnOnce(Area) -> Area,
-    {
-        self.padding = f(self.padding);
+    pub fn attributes(mut self, attributes: Attributes) -> Self {
+        self.attributes = attributes;
         self
     }
 
-    pub fn margin<F>(mut self, f: F) -> Self
-    where
-        F: FnOnce(Area) -> Area,
-    {
-        self.margin = f(self.margin);
+    /// A cell showing `symbol` in `style`, in the default colors where the
+    /// style has none.
+    pub fn styled<S: Into<String>>(symbol: S, style: Style) -> Self {
+        let mut px = Self::new(symbol, Color::White, Color::Reset);
+        style.apply(&mut px);
+        px
+    }
+
+    pub fn underline_color(mut self, color: Color) -> Self {
+        self.underline_color = Some(color);
         self
     }
 
-    pub fn render(&self, stdout: &mut Stdout) {
-        let fg = if self.clicked {
-            Color::Red
-        } else {
-            Color::Black
-        };
+    /// Display columns taken by the symbol: 0, 1 or 2.
+    pub fn width(&self) -> u16 {
+        grapheme_width(&self.symbol)
+    }
 
-        // margin
-        let mut row = self.pos.1;
-        while row < self.size.1 + self.pos.1 {
-            queue!(
-                stdout,
-                cursor::MoveTo(self.pos.0, row),
-                SetForegroundColor(self.margin.color),
-                // TODO: make symbol a string and only accept char to set symbol?
-                Print(self.margin.symbol.to_string().repeat(
-                    (self.margin.left
-                        + self.margin.right
-                        + self.padding.left
-                        + self.padding.right
-                        + self.text.len() as u16) as usize
-                ))
-            )
-            .unwrap();
-            row += 1;
+    fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+}
+
+fn grapheme_width(grapheme: &str) -> u16 {
+    cmp::min(grapheme.width(), 2) as u16
+}
+
+/// Width of `text` in terminal columns, measured per grapheme cluster.
+pub fn display_width(text: &str) -> u16 {
+    text.graphemes(true)
+        .map(grapheme_width)
+        .fold(0, u16::saturating_add)
+}
+
+/// Attributes that share an SGR "off" code, paired with that code.
+const ATTRIBUTE_GROUPS: [(Attribute, &[Attribute]); 7] = [
+    (
+        Attribute::NormalIntensity,
+        &[Attribute::Bold, Attribute::Dim],
+    ),
+    (Attribute::NoItalic, &[Attribute::Italic]),
+    (
+        Attribute::NoUnderline,
+        &[
+            Attribute::Underlined,
+            Attribute::DoubleUnderlined,
+            Attribute::Undercurled,
+            Attribute::Underdotted,
+            Attribute::Underdashed,
+        ],
+    ),
+    (
+        Attribute::NoBlink,
+        &[Attribute::SlowBlink, Attribute::RapidBlink],
+    ),
+    (Attribute::NoReverse, &[Attribute::Reverse]),
+    (Attribute::NoHidden, &[Attribute::Hidden]),
+    (Attribute::NotCrossedOut, &[Attribute::CrossedOut]),
+];
+
+/// The style the terminal is currently drawing with, so the flush only emits
+/// what changes between consecutive cells.
+#[derive(Clone, Debug, PartialEq)]
+struct Pen {
+    color: Color,
+    background: Color,
+    attributes: Attributes,
+    underline_color: Option<Color>,
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        Self {
+            color: Color::Reset,
+            background: Color::Reset,
+            attributes: Attributes::default(),
+            underline_color: None,
         }
-        // padding
-        let mut row = self.pos.1 + self.margin.top;
-        while row < self.pos.1 + self.size.1 - self.margin.top {
-            queue!(
-                stdout,
-                cursor::MoveTo(self.pos.0 + self.margin.left, row),
-                SetForegroundColor(self.padding.color),
-                Print(self.padding.symbol.to_string().repeat(
-                    (self.padding.left + self.padding.right + self.text.len() as u16) as usize
-                ))
-            )
-            .unwrap();
-            row += 1;
-        }
-
-        queue!(
-            stdout,
-            FG_RESET,
-            BG_RESET,
-            cursor::MoveTo(
-                self.pos.0 + self.margin.left + self.padding.left,
-                self.pos.1 + self.margin.top + self.padding.top
-            ),
-            // Print(" ".repeat(self.margin.0.into())),
-            SetBackgroundColor(Color::White),
-            SetForegroundColor(fg),
-            // Print(" ".repeat(self.padding.0.into())),
-            Print(&self.text),
-            // Print(format!(
-            //     "{:?} {:?} {:?}",
-            //     self.padding, self.margin, self.pos
-            // )),
-            // Print(" ".repeat(self.padding.0.into())),
-            FG_RESET,
-            BG_RESET,
-            // Print(" ".repeat(self.margin.0.into())),
-        )
-        .unwrap();
+    }
+}
 
-        stdout.flush().unwrap();
+impl Pen {
+    fn matches(&self, px: &Pixel) -> bool {
+        self.color == px.color
+            && self.background == px.background
+            && self.attributes == px.attributes
+            && self.underline_color == px.underline_color
     }
 
-    pub fn calc_self(&mut self) -> (u16, u16) {
-        // only supports 1-height text for now
-        self.size.0 = self.margin.left
-            + self.margin.right
-            + self.padding.left
-            + self.padding.right
-            + self.text.len() as u16;
-        self.size.1 = self.margin.top * 2 + self.padding.top * 2 + 1;
-        self.size
+    fn apply<B: Backend>(&mut self, backend: &mut B, px: &Pixel) -> io::Result<()> {
+        if self.color != px.color {
+            backend.set_foreground(px.color)?;
+        }
+        if self.background != px.background {
+            backend.set_background(px.background)?;
+        }
+        if self.attributes != px.attributes {
+            for (off, members) in ATTRIBUTE_GROUPS {
+                let removed = members
+                    .iter()
+                    .any(|a| self.attributes.has(*a) && !px.attributes.has(*a));
+                if removed {
+                    backend.set_attribute(off)?;
+                }
+                for a in members {
+                    if px.attributes.has(*a) && (removed || !self.attributes.has(*a)) {
+                        backend.set_attribute(*a)?;
+                    }
+                }
+            }
+        }
+        if self.underline_color != px.underline_color {
+            backend.set_underline_color(px.underline_color.unwrap_or(Color::Reset))?;
+        }
+        self.color = px.color;
+        self.background = px.background;
+        self.attributes = px.attributes;
+        self.underline_color = px.underline_color;
+        Ok(())
     }
 
-    pub fn process(&mut self, click_pos: Option<(u16, u16)>) {
-        if let Some((click_col, click_row)) = click_pos {
-            if click_col >= self.pos.0 + self.margin.left
-                && click_row >= self.pos.1 + self.margin.top
-                && click_col < self.pos.0 + self.size.0 - self.margin.right
-                && click_row < self.pos.1 + self.size.1 - self.margin.bottom
-            {
-                self.clicked = true;
-            };
-        } else {
-            self.clicked = false;
+    /// Returns the terminal to its default style.
+    fn reset<B: Backend>(&mut self, backend: &mut B) -> io::Result<()> {
+        if *self != Pen::default() {
+            backend.set_attribute(Attribute::Reset)?;
         }
+        *self = Pen::default();
+        Ok(())
+    }
+}
+
+impl Default for Pixel {
+    fn default() -> Self {
+        Self::new(' ', Color::White, Color::Reset)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Context {
+    pub max: (u16, u16),
+    pub focus: FocusManager,
+    /// The element a mouse button went down on, until it is released.
+    pub pressed: Option<ElementPath>,
+    /// The element under the pointer.
+    pub hovered: Option<ElementPath>,
+    pub mouse: MouseState,
+    /// The event `UI::process` has yet to route.
+    event: Option<Event>,
+    // pub offset: u8,
+    /// The styles elements draw themselves with.
+    pub theme: Theme,
+    /// Restyles elements before every layout.
+    pub stylesheet: Stylesheet,
+    /// The colors the terminal can show. Set it to override detection.
+    pub color_support: ColorSupport,
+    /// The frame being drawn.
+    virtual_display: VirtualDisplay,
+    /// The frame currently on screen.
+    front: VirtualDisplay,
+    /// Repaint every cell on the next render, regardless of `front`.
+    force_redraw: bool,
+    /// Top row of the viewport in `ScreenMode::Inline`, found by `UI::enter`.
+    inline_row: u16,
+    /// Something changed that the screen does not show yet.
+    redraw: bool,
+    /// How long the terminal has to stop resizing before the new size is
+    /// applied. Zero applies every resize immediately.
+    pub resize_debounce: Duration,
+    /// The latest size reported by a resize event and when it arrived.
+    pending_resize: Option<((u16, u16), Instant)>,
+}
+
+#[derive(Clone, Debug)]
+struct VirtualDisplay(Vec<VirtualDisplayRow>);
+
+#[derive(Clone, Debug)]
+struct VirtualDisplayRow(Vec<Pixel>);
+
+impl VirtualDisplay {
+    fn new((width, height): (u16, u16)) -> Self {
+        VirtualDisplay(vec![
+            VirtualDisplayRow(vec![Pixel::default(); width.into()]);
+            height.into()
+        ])
+    }
+
+    /// Writes a cell. Cells outside the display are ignored. A double-width
+    /// symbol also claims the cell to its right, and any wide symbol that
+    /// gets partially overwritten is blanked out.
+    fn put(&mut self, col: u16, row: u16, mut px: Pixel) {
+        let Some(row) = self.0.get_mut(row as usize) else {
+            return;
+        };
+        let col = col as usize;
+        if col >= row.0.len() {
+            return;
+        }
+        if px.width() > 1 && col + 1 >= row.0.len() {
+            px.symbol = " ".into();
+        }
+
+        row.split_wide(col);
+        if px.width() > 1 {
+            row.split_wide(col + 1);
+            row.0[col + 1] = Pixel {
+                symbol: String::new(),
+                ..px.clone()
+            };
+        }
+        row.0[col] = px;
+    }
+
+    fn get(&self, col: u16, row: u16) -> Option<&Pixel> {
+        self.0.get(row as usize).and_then(|r| r.0.get(col as usize))
+    }
+
+    fn get_mut(&mut self, col: u16, row: u16) -> Option<&mut Pixel> {
+        self.0
+            .get_mut(row as usize)
+            .and_then(|r| r.0.get_mut(col as usize))
+    }
+
+    fn clear(&mut self) {
+        for row in self.0.iter_mut() {
+            row.0.fill(Pixel::default());
+        }
+    }
+}
+
+/// A rectangle of cells. ORDER IS: COLUMN, ROW
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub col: u16,
+    pub row: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(pos: (u16, u16), size: (u16, u16)) -> Self {
+        Self {
+            col: pos.0,
+            row: pos.1,
+            width: size.0,
+            height: size.1,
+        }
+    }
+
+    /// One past the last column.
+    pub fn right(&self) -> u16 {
+        self.col.saturating_add(self.width)
+    }
+
+    /// One past the last row.
+    pub fn bottom(&self) -> u16 {
+        self.row.saturating_add(self.height)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, (col, row): (u16, u16)) -> bool {
+        col >= self.col && col < self.right() && row >= self.row && row < self.bottom()
+    }
+
+    pub fn intersection(&self, other: Rect) -> Rect {
+        let col = cmp::max(self.col, other.col);
+        let row = cmp::max(self.row, other.row);
+        Rect {
+            col,
+            row,
+            width: cmp::min(self.right(), other.right()).saturating_sub(col),
+            height: cmp::min(self.bottom(), other.bottom()).saturating_sub(row),
+        }
+    }
+
+    /// Shrinks the rectangle by the given amount on each side.
+    pub fn inset(&self, top: u16, right: u16, bottom: u16, left: u16) -> Rect {
+        Rect {
+            col: self.col.saturating_add(left),
+            row: self.row.saturating_add(top),
+            width: self.width.saturating_sub(left.saturating_add(right)),
+            height: self.height.saturating_sub(top.saturating_add(bottom)),
+        }
+    }
+}
+
+/// Where elements draw. Every write is clipped to `clip`, which children
+/// inherit from their parent's content area, so overflowing content is cut
+/// off instead of spilling into siblings or off the screen.
+pub struct Canvas<'c> {
+    display: &'c mut VirtualDisplay,
+    clip: Rect,
+    theme: &'c Theme,
+    /// What is left of the focused element's path below this element.
+    focus: Option<&'c [usize]>,
+    /// What is left of the pressed element's path below this element.
+    pressed: Option<&'c [usize]>,
+    /// What is left of the hovered element's path below this element.
+    hovered: Option<&'c [usize]>,
+}
+
+/// The rest of `path` below the child at `index`, if `path` goes through it.
+fn descend(path: Option<&[usize]>, index: usize) -> Option<&[usize]> {
+    path.and_then(|path| path.split_first())
+        .and_then(|(head, rest)| (*head == index).then_some(rest))
+}
+
+impl<'c> Canvas<'c> {
+    fn new(
+        display: &'c mut VirtualDisplay,
+        clip: Rect,
+        theme: &'c Theme,
+        focus: Option<&'c [usize]>,
+        pressed: Option<&'c [usize]>,
+        hovered: Option<&'c [usize]>,
+    ) -> Self {
+        Self {
+            display,
+            clip,
+            theme,
+            focus,
+            pressed,
+            hovered,
+        }
+    }
+
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    pub fn theme(&self) -> &'c Theme {
+        self.theme
+    }
+
+    /// Whether the element drawing on this canvas has keyboard focus.
+    pub fn is_focused(&self) -> bool {
+        self.focus.is_some_and(|path| path.is_empty())
+    }
+
+    /// Whether the element drawing on this canvas is being pressed.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed.is_some_and(|path| path.is_empty())
+    }
+
+    /// Whether the pointer is over the element drawing on this canvas.
+    pub fn is_hovered(&self) -> bool {
+        self.hovered.is_some_and(|path| path.is_empty())
+    }
+
+    /// Clipped to both `rect` and this canvas' clip.
+    pub fn clipped(&mut self, rect: Rect) -> Canvas<'_> {
+        Canvas {
+            clip: self.clip.intersection(rect),
+            display: self.display,
+            theme: self.theme,
+            focus: self.focus,
+            pressed: self.pressed,
+            hovered: self.hovered,
+        }
+    }
+
+    /// The canvas for the child at `index` of the element drawing on this one.
+    pub fn child(&mut self, index: usize) -> Canvas<'_> {
+        Canvas {
+            clip: self.clip,
+            display: self.display,
+            theme: self.theme,
+            focus: descend(self.focus, index),
+            pressed: descend(self.pressed, index),
+            hovered: descend(self.hovered, index),
+        }
+    }
+
+    /// Changes the cells of `rect` in place, e.g. to highlight them.
+    pub fn update<F: FnMut(&mut Pixel)>(&mut self, rect: Rect, mut f: F) {
+        let rect = self.clip.intersection(rect);
+        for row in rect.row..rect.bottom() {
+            for col in rect.col..rect.right() {
+                if let Some(px) = self.display.get_mut(col, row) {
+                    f(px);
+                }
+            }
+        }
+    }
+
+    /// The cell at `col`, `row`, if it is inside the clip.
+    pub fn get(&self, col: u16, row: u16) -> Option<&Pixel> {
+        if !self.clip.contains((col, row)) {
+            return None;
+        }
+        self.display.get(col, row)
+    }
+
+    pub fn put(&mut self, col: u16, row: u16, mut px: Pixel) {
+        if !self.clip.contains((col, row)) {
+            return;
+        }
+        if px.width() > 1 && !self.clip.contains((col.saturating_add(1), row)) {
+            px.symbol = " ".into();
+        }
+        self.display.put(col, row, px);
+    }
+
+    /// Prints `text` on one row, one grapheme per cell (two for wide ones).
+    pub fn print(&mut self, col: u16, row: u16, text: &str, color: Color, background: Color) {
+        self.print_styled(col, row, text, &Pixel::new(' ', color, background));
+    }
+
+    /// Prints `text` in the colors and attributes of `style`, a cell whose
+    /// symbol is ignored. Returns the column after the text.
+    pub fn print_styled(&mut self, col: u16, row: u16, text: &str, style: &Pixel) -> u16 {
+        let mut col = col;
+        for grapheme in text.graphemes(true) {
+            let px = Pixel {
+                symbol: grapheme.to_string(),
+                ..style.clone()
+            };
+            let width = px.width();
+            if width == 0 {
+                continue;
+            }
+            if col >= self.clip.right() {
+                break;
+            }
+            self.put(col, row, px);
+            col = col.saturating_add(width);
+        }
+        col
+    }
+
+    pub fn fill(&mut self, rect: Rect, px: Pixel) {
+        let rect = self.clip.intersection(rect);
+        for row in rect.row..rect.bottom() {
+            for col in rect.col..rect.right() {
+                self.display.put(col, row, px.clone());
+            }
+        }
+    }
+}
+
+impl VirtualDisplayRow {
+    /// Blanks whichever half of a double-width symbol is left behind when
+    /// the cell at `col` is about to be overwritten.
+    fn split_wide(&mut self, col: usize) {
+        if self.0[col].is_continuation() && col > 0 {
+            self.0[col - 1].symbol = " ".into();
+        } else if self.0[col].width() > 1 {
+            if let Some(next) = self.0.get_mut(col + 1) {
+                next.symbol = " ".into();
+            }
+        }
+    }
+}
+
+impl Index<u16> for VirtualDisplay {
+    type Output = VirtualDisplayRow;
+
+    fn index(&self, index: u16) -> &Self::Output {
+        &self.0[index as usize]
+    }
+}
+
+impl IndexMut<u16> for VirtualDisplay {
+    fn index_mut(&mut self, index: u16) -> &mut Self::Output {
+        &mut self.0[index as usize]
+    }
+}
+
+impl Index<u16> for VirtualDisplayRow {
+    type Output = Pixel;
+
+    fn index(&self, index: u16) -> &Self::Output {
+        &self.0[index as usize]
+    }
+}
+
+impl IndexMut<u16> for VirtualDisplayRow {
+    fn index_mut(&mut self, index: u16) -> &mut Self::Output {
+        &mut self.0[index as usize]
+    }
+}
+
+impl Context {
+    /// Creates a context the size of the terminal, drawing in the colors
+    /// `ColorSupport::detect` finds.
+    pub fn new() -> Result<Self> {
+        let size = size().map_err(Error::TerminalUnavailable)?;
+        Ok(Self {
+            color_support: ColorSupport::detect(),
+            ..Self::with_size(size)
+        })
+    }
+
+    /// Creates a context without asking the terminal for its size, for
+    /// headless rendering. Colors are drawn as they are.
+    pub fn with_size((width, height): (u16, u16)) -> Self {
+        Context {
+            focus: FocusManager::default(),
+            theme: Theme::default(),
+            stylesheet: Stylesheet::default(),
+            color_support: ColorSupport::default(),
+            pressed: None,
+            hovered: None,
+            mouse: MouseState::default(),
+            event: None,
+            max: (width, height),
+            virtual_display: VirtualDisplay::new((width, height)),
+            front: VirtualDisplay::new((width, height)),
+            force_redraw: true,
+            inline_row: 0,
+            redraw: true,
+            resize_debounce: Duration::from_millis(50),
+            pending_resize: None,
+        }
+    }
+
+    /// Resizes both buffers and repaints everything on the next render.
+    pub fn set_size(&mut self, new_size: (u16, u16)) {
+        self.max = new_size;
+        self.virtual_display = VirtualDisplay::new(new_size);
+        self.front = VirtualDisplay::new(new_size);
+        self.force_redraw = true;
+        self.pending_resize = None;
+    }
+
+    /// Applies a pending resize once the terminal has been quiet for
+    /// `resize_debounce`. Returns whether the size changed.
+    pub fn apply_pending_resize(&mut self) -> bool {
+        match self.pending_resize {
+            Some((new_size, at)) if at.elapsed() >= self.resize_debounce => {
+                if new_size != self.max {
+                    self.set_size(new_size);
+                    return true;
+                }
+                self.pending_resize = None;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Asks for the next frame to be drawn.
+    pub fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Whether anything asked for a redraw since the last render.
+    pub fn needs_redraw(&self) -> bool {
+        self.redraw || self.force_redraw
+    }
+
+    /// How long until a pending resize settles, so event loops can poll with
+    /// this timeout and render once it has passed.
+    pub fn resize_timeout(&self) -> Option<Duration> {
+        self.pending_resize
+            .map(|(_, at)| self.resize_debounce.saturating_sub(at.elapsed()))
+    }
+
+    pub fn process(&mut self, event: &Event) {
+        if let Event::Resize(width, height) = *event {
+            self.pending_resize = Some(((width, height), Instant::now()));
+            self.apply_pending_resize();
+        }
+
+        match event {
+            Event::Mouse(mouse) => self.mouse.update(mouse),
+            _ => self.mouse.scroll = None,
+        }
+        self.event = Some(event.clone());
+    }
+}
+
+impl<'a, B: Backend> UI<'a, B> {
+    pub fn new(backend: B, root: Block<'a>) -> Self {
+        Self {
+            backend,
+            root,
+            screen: ScreenMode::Fullscreen,
+            keep_final_frame: None,
+        }
+    }
+
+    pub fn screen(mut self, screen: ScreenMode) -> Self {
+        self.screen = screen;
+        self
+    }
+
+    /// Whether `exit` leaves the last frame on the main screen, where it
+    /// ends up in scrollback. Defaults to no for `Fullscreen` and yes for
+    /// the others.
+    pub fn keep_final_frame(mut self, keep: bool) -> Self {
+        self.keep_final_frame = Some(keep);
+        self
+    }
+
+    /// The part of the terminal the UI draws on.
+    pub fn viewport(&self, ctx: &Context) -> Rect {
+        let screen = Rect::new((0, 0), ctx.max);
+        match self.screen {
+            ScreenMode::Fullscreen => screen,
+            ScreenMode::Inline(lines) => {
+                // Keep the viewport on screen if the terminal shrank.
+                let row = cmp::min(ctx.inline_row, ctx.max.1.saturating_sub(lines));
+                screen.intersection(Rect::new((0, row), (ctx.max.0, lines)))
+            }
+            ScreenMode::Fixed(rect) => screen.intersection(rect),
+        }
+    }
+
+    /// Prepares the terminal for the screen mode: switches to the alternate
+    /// screen, or makes room for an inline viewport below the cursor.
+    pub fn enter(&mut self, ctx: &mut Context) -> Result<()> {
+        match self.screen {
+            ScreenMode::Fullscreen => self.backend.enter_alternate_screen()?,
+            ScreenMode::Inline(lines) => {
+                // Scroll the screen up if there are not enough lines left.
+                self.backend
+                    .print(&"\n".repeat(lines.saturating_sub(1).into()))?;
+                let (_, row) = self.backend.cursor_position()?;
+                ctx.inline_row = row.saturating_sub(lines.saturating_sub(1));
+            }
+            ScreenMode::Fixed(_) => {}
+        }
+        ctx.force_redraw = true;
+        self.backend.flush()?;
+        Ok(())
+    }
+
+    /// Hands the terminal back. The last frame is either wiped or left in
+    /// place with the cursor below it, see `keep_final_frame`.
+    pub fn exit(&mut self, ctx: &mut Context) -> Result<()> {
+        let area = self.viewport(ctx);
+        let keep = self
+            .keep_final_frame
+            .unwrap_or(self.screen != ScreenMode::Fullscreen);
+        if self.screen == ScreenMode::Fullscreen {
+            self.backend.leave_alternate_screen()?;
+            if keep {
+                print_frame(&mut self.backend, &ctx.front, area, ctx.color_support)?;
+            }
+        } else if keep {
+            self.backend.move_to(0, area.bottom().saturating_sub(1))?;
+            self.backend.print("\r\n")?;
+        } else {
+            let blank = VirtualDisplay::new(ctx.max);
+            flush_diff(
+                &mut self.backend,
+                &ctx.front,
+                &blank,
+                false,
+                area,
+                ctx.color_support,
+            )?;
+            ctx.front = blank;
+            self.backend.move_to(area.col, area.row)?;
+        }
+        self.backend.flush()?;
+        Ok(())
+    }
+
+    /// Styles and arranges the tree in the viewport without drawing it, so
+    /// `process` can route input through it.
+    pub fn layout(&mut self, ctx: &mut Context) -> Result<()> {
+        ctx.stylesheet.restyle(
+            &mut self.root,
+            &mut vec![],
+            ctx.focus.focused(),
+            ctx.hovered.as_deref(),
+            ctx.pressed.as_deref(),
+        );
+        validate(&self.root)?;
+        let (width, height) = self.root.measure();
+        if width == u16::MAX || height == u16::MAX {
+            return Err(Error::LayoutOverflow);
+        }
+        let area = self.viewport(ctx);
+        self.root.arrange(area);
+        ctx.focus.rebuild(&self.root);
+        Ok(())
+    }
+
+    /// Lays out and draws the tree, then writes whatever changed since the
+    /// last frame to the backend.
+    pub fn render(&mut self, ctx: &mut Context) -> Result<()> {
+        ctx.apply_pending_resize();
+        self.layout(ctx)?;
+        // Outside the viewport is not ours to clear; repainting every cell
+        // of the viewport does the same job.
+        if ctx.force_redraw && self.screen == ScreenMode::Fullscreen {
+            self.backend.clear()?;
+        }
+        let area = self.viewport(ctx);
+        ctx.virtual_display.clear();
+        self.root.render(&mut Canvas::new(
+            &mut ctx.virtual_display,
+            area,
+            &ctx.theme,
+            ctx.focus.focused(),
+            ctx.pressed.as_deref(),
+            ctx.hovered.as_deref(),
+        ));
+
+        flush_diff(
+            &mut self.backend,
+            &ctx.front,
+            &ctx.virtual_display,
+            ctx.force_redraw,
+            area,
+            ctx.color_support,
+        )?;
+        mem::swap(&mut ctx.front, &mut ctx.virtual_display);
+        ctx.force_redraw = false;
+        ctx.redraw = false;
+        Ok(())
+    }
+
+    /// Routes the event recorded by `Context::process` through the tree.
+    /// Mouse events go to the topmost element under the pointer, key and
+    /// paste events to the focused element (or the root if nothing has
+    /// focus). Keys nobody handles fall back to moving focus.
+    pub fn process(&mut self, ctx: &mut Context) -> Result<()> {
+        validate(&self.root)?;
+        ctx.focus.rebuild(&self.root);
+        let Some(event) = ctx.event.take() else {
+            return Ok(());
+        };
+        let focused = ctx.focus.focused().map(<[usize]>::to_vec);
+        let pressed = ctx.pressed.clone();
+
+        match event {
+            Event::Mouse(mouse) => {
+                let target = hit_test(&self.root, (mouse.column, mouse.row));
+                if target != ctx.hovered {
+                    if let Some(path) = ctx.hovered.take() {
+                        self.route(ctx, UiEvent::Leave, path);
+                    }
+                    if let Some(path) = target.clone() {
+                        self.route(ctx, UiEvent::Enter, path);
+                    }
+                    ctx.hovered = target.clone();
+                    ctx.request_redraw();
+                }
+                let mut click = None;
+                match mouse.kind {
+                    MouseEventKind::Down(_) => {
+                        ctx.pressed = target.clone();
+                        if let Some(target) = &target {
+                            ctx.focus.focus_within(target);
+                        }
+                    }
+                    // Only a release over the element that was pressed counts
+                    // as a click.
+                    MouseEventKind::Up(button) if ctx.pressed.take() == target => {
+                        click = Some(UiEvent::Click {
+                            button,
+                            count: ctx.mouse.click_count,
+                            column: mouse.column,
+                            row: mouse.row,
+                        });
+                    }
+                    _ => {}
+                }
+                if let Some(target) = target {
+                    self.route(ctx, UiEvent::Mouse(mouse), target.clone());
+                    if let Some(click) = click {
+                        self.route(ctx, click, target);
+                    }
+                }
+            }
+            Event::Key(key) if key.kind != KeyEventKind::Release => {
+                let target = focused.clone().unwrap_or_default();
+                if !self.route(ctx, UiEvent::Key(key), target) {
+                    default_key_action(&mut ctx.focus, &key);
+                }
+            }
+            Event::Paste(text) => {
+                let target = focused.clone().unwrap_or_default();
+                self.route(ctx, UiEvent::Paste(text), target);
+            }
+            _ => {}
+        }
+
+        let now_focused = ctx.focus.focused().map(<[usize]>::to_vec);
+        if focused != now_focused {
+            if let Some(path) = focused {
+                self.route(ctx, UiEvent::Blur, path);
+            }
+            if let Some(path) = now_focused {
+                self.route(ctx, UiEvent::Focus, path);
+            }
+            ctx.request_redraw();
+        }
+        if pressed != ctx.pressed {
+            ctx.request_redraw();
+        }
+        Ok(())
+    }
+
+    /// Dispatches `event` to `target`. Returns whether it was handled.
+    fn route(&mut self, ctx: &mut Context, event: UiEvent, target: ElementPath) -> bool {
+        let mut cx = EventContext::new(event, target);
+        let path = cx.target.clone();
+        dispatch(&mut self.root, &path, &mut cx);
+        if cx.redraw_requested() {
+            ctx.request_redraw();
+        }
+        cx.is_handled()
+    }
+}
+
+/// Checks every element in the tree with `Element::validate`.
+fn validate(el: &dyn Element) -> Result<()> {
+    el.validate()?;
+    (0..el.child_count())
+        .filter_map(|i| el.child(i))
+        .try_for_each(validate)
+}
+
+/// What a key does when no element handles it: Tab and Shift-Tab cycle
+/// focus, plain arrow keys move it spatially.
+fn default_key_action(focus: &mut FocusManager, key: &KeyEvent) {
+    let direction = match key.code {
+        KeyCode::Tab => return focus.focus_next(),
+        KeyCode::BackTab => return focus.focus_prev(),
+        KeyCode::Up => FocusDirection::Up,
+        KeyCode::Down => FocusDirection::Down,
+        KeyCode::Left => FocusDirection::Left,
+        KeyCode::Right => FocusDirection::Right,
+        _ => return,
+    };
+    if key.modifiers == KeyModifiers::NONE {
+        focus.focus_towards(direction);
+    }
+}
+
+/// Prints the rows of `area` in `display` one after another from the
+/// cursor, for when the cursor position is not known.
+fn print_frame<B: Backend>(
+    backend: &mut B,
+    display: &VirtualDisplay,
+    area: Rect,
+    colors: ColorSupport,
+) -> io::Result<()> {
+    let mut pen = Pen::default();
+    for row in area.row..area.bottom() {
+        for col in area.col..area.right() {
+            let px = &display[row][col];
+            if px.is_continuation() {
+                continue;
+            }
+            let px = colors.adapt(px);
+            if !pen.matches(&px) {
+                pen.apply(backend, &px)?;
+            }
+            backend.print(&px.symbol)?;
+        }
+        pen.reset(backend)?;
+        backend.print("\r\n")?;
+    }
+    backend.flush()
+}
+
+/// Emits the cells of `back` that differ from `front`. Adjacent changed
+/// cells are printed as one run, the cursor is only moved when it is not
+/// already in place, and styles are only set when they change. Colors are
+/// brought down to what `colors` allows on the way out.
+fn flush_diff<B: Backend>(
+    backend: &mut B,
+    front: &VirtualDisplay,
+    back: &VirtualDisplay,
+    force: bool,
+    area: Rect,
+    colors: ColorSupport,
+) -> io::Result<()> {
+    let mut pen = Pen::default();
+    let mut cursor = None;
+    let mut run = String::new();
+    for (row_pos, (front_row, back_row)) in front.0.iter().zip(back.0.iter()).enumerate() {
+        for (col_pos, (old, px)) in front_row.0.iter().zip(back_row.0.iter()).enumerate() {
+            let pos = (col_pos as u16, row_pos as u16);
+            // Printing the left half of a wide symbol covers this cell.
+            if !area.contains(pos) || px.is_continuation() || (!force && old == px) {
+                continue;
+            }
+            let px = colors.adapt(px);
+            if cursor != Some(pos) || !pen.matches(&px) {
+                if !run.is_empty() {
+                    backend.print(&run)?;
+                    run.clear();
+                }
+                if cursor != Some(pos) {
+                    backend.move_to(pos.0, pos.1)?;
+                }
+                pen.apply(backend, &px)?;
+            }
+            run.push_str(&px.symbol);
+            cursor = Some((pos.0 + px.width(), pos.1));
+        }
+    }
+    if !run.is_empty() {
+        backend.print(&run)?;
+    }
+    pen.reset(backend)?;
+    backend.flush()
+}
+
+/// Anything that can live in a `Block`. `Block` and `Widget` implement it,
+/// and so can widgets from other crates.
+pub trait Element {
+    /// The size the element wants, including its margins. Must not change
+    /// any state: nothing is positioned until `arrange`.
+    fn measure(&self) -> (u16, u16);
+
+    /// Gives the element its final, absolute rectangle.
+    fn arrange(&mut self, rect: Rect);
+
+    /// The rectangle from the last `arrange`.
+    fn rect(&self) -> Rect;
+
+    /// Draws the element. The canvas is already clipped to the parent's
+    /// content area.
+    fn render(&self, canvas: &mut Canvas);
+
+    /// Reacts to an event routed through this element. Ancestors of the
+    /// target see it twice, once in `Phase::Capture` on the way down and
+    /// once in `Phase::Bubble` on the way back up; the target itself sees
+    /// it in `Phase::Target`. Call `cx.stop_propagation()` to keep it from
+    /// going any further.
+    fn handle_event(&mut self, _cx: &mut EventContext) {}
+
+    /// Whether `pos` lands on this element, for mouse events. Children are
+    /// tested before their parent.
+    fn hit(&self, pos: (u16, u16)) -> bool {
+        self.rect().contains(pos)
+    }
+
+    /// Whether the element can take keyboard focus.
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn child_count(&self) -> usize {
+        0
+    }
+
+    fn child(&self, _index: usize) -> Option<&dyn Element> {
+        None
+    }
+
+    fn child_mut(&mut self, _index: usize) -> Option<&mut dyn Element> {
+        None
+    }
+
+    /// How much room to take along the parent's direction.
+    fn constraint(&self) -> Constraint {
+        Constraint::Auto
+    }
+
+    /// Leading and trailing margin along `direction`. Margins of neighbouring
+    /// elements collapse into each other.
+    fn margins(&self, _direction: Direction) -> (u16, u16) {
+        (0, 0)
+    }
+
+    /// Whether the margins are a border shared with neighbours that merge
+    /// theirs too. Margins only collapse between elements that agree.
+    fn merges_border(&self) -> bool {
+        false
+    }
+
+    /// The height the element needs when it is `width` wide, margins
+    /// included. Only elements whose height depends on their width, such
+    /// as wrapping text, need to override it.
+    fn height_for(&self, _width: u16) -> u16 {
+        self.measure().1
+    }
+
+    /// Whether to fill the parent across its direction instead of keeping the
+    /// measured size.
+    fn stretch(&self) -> bool {
+        false
+    }
+
+    /// Checks the element's settings before it is laid out. Children are
+    /// checked separately.
+    fn validate(&self) -> Result<()> {
+        self.constraint().validate()
+    }
+
+    /// The name stylesheets select this kind of element by, such as
+    /// `widget`.
+    fn type_name(&self) -> &str {
+        ""
+    }
+
+    /// The `#id` stylesheets select the element by.
+    fn id(&self) -> Option<&str> {
+        None
+    }
+
+    /// The `.class`es stylesheets select the element by.
+    fn classes(&self) -> &[String] {
+        &[]
+    }
+
+    /// Takes what the stylesheet sets on the element in its current state.
+    /// Called before every layout.
+    fn restyle(&mut self, _properties: Properties) {}
+}
+
+/// How much room an element takes along its parent's direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// As much as its content needs.
+    Auto,
+    Fixed(u16),
+    /// Percentage of the parent's inner size.
+    Percentage(u16),
+    /// At least this much, more if the content needs it.
+    Min(u16),
+    /// At most this much, less if the content needs less.
+    Max(u16),
+    /// A share of whatever space the other children leave, weighted
+    /// against the other `Fill`s.
+    Fill(u16),
+}
+
+impl Constraint {
+    pub fn validate(self) -> Result<()> {
+        match self {
+            Constraint::Percentage(p) if p > 100 => Err(Error::InvalidConfig(format!(
+                "percentage constraint of {p}, must be at most 100"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Size before `Fill`s get their share of the leftover space.
+    fn base(self, available: u16, measured: u16) -> u16 {
+        match self {
+            Constraint::Auto => measured,
+            Constraint::Fixed(n) => n,
+            Constraint::Percentage(p) => (u32::from(available) * u32::from(p) / 100) as u16,
+            Constraint::Min(n) => cmp::max(n, measured),
+            Constraint::Max(n) => cmp::min(n, measured),
+            Constraint::Fill(_) => 0,
+        }
+    }
+}
+
+/// Sizes children along the main axis: everything gets its base size, then
+/// `Fill`s split what is left by weight.
+fn distribute(available: u16, items: &[(Constraint, u16)]) -> Vec<u16> {
+    let mut sizes: Vec<u16> = items
+        .iter()
+        .map(|(constraint, measured)| constraint.base(available, *measured))
+        .collect();
+    let used = sizes.iter().fold(0, |acc: u16, s| acc.saturating_add(*s));
+    let remaining = u32::from(available.saturating_sub(used));
+    let total_weight: u32 = items
+        .iter()
+        .map(|(constraint, _)| match constraint {
+            Constraint::Fill(weight) => u32::from(*weight),
+            _ => 0,
+        })
+        .sum();
+    if total_weight == 0 {
+        return sizes;
+    }
+
+    let mut handed_out = 0;
+    let mut weight_so_far = 0;
+    for (size, (constraint, _)) in sizes.iter_mut().zip(items) {
+        if let Constraint::Fill(weight) = constraint {
+            // Hand out cumulative shares so rounding never loses a cell.
+            weight_so_far += u32::from(*weight);
+            let share = remaining * weight_so_far / total_weight;
+            *size = (share - handed_out) as u16;
+            handed_out = share;
+        }
+    }
+    sizes
+}
+
+/// ORDER IS: COLUMN, ROW
+/// WIDTH, HEIGHT
+pub struct Block<'a> {
+    contents: Vec<Box<dyn Element + 'a>>,
+    // parent: Option<Rc<Block<'a>>>,
+    /// Set by `arrange`.
+    pub pos: (u16, u16),
+    /// Set by `arrange`.
+    pub size: (u16, u16),
+    pub direction: Direction,
+    /// Empty cells between consecutive children.
+    pub gap: u16,
+    pub constraint: Constraint,
+    pub border: Border,
+    /// Laid over the theme's surface style for the inside of the block.
+    pub style: Style,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    /// Set by the stylesheet.
+    sheet: Properties,
+    handlers: Handlers<'a>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+impl Element for Block<'_> {
+    fn render(&self, canvas: &mut Canvas) {
+        let rect = self.rect();
+        let border = self.styled_border();
+        let (top, right, bottom, left) = border.thickness();
+        let surface = canvas
+            .theme()
+            .surface
+            .patch(self.style)
+            .patch(self.sheet.style);
+        canvas.fill(
+            rect.inset(top, right, bottom, left),
+            Pixel::styled(' ', surface),
+        );
+        border.render(canvas, rect);
+
+        let mut canvas = canvas.clipped(self.content_rect());
+        for (i, el) in self.contents.iter().enumerate() {
+            el.render(&mut canvas.child(i));
+        }
+    }
+
+    /// The space the block needs to fit all children along its direction.
+    fn measure(&self) -> (u16, u16) {
+        let (main, cross) = self.fit(&self.measure_children(None));
+        let (top, right, bottom, left) = self.styled_border().thickness();
+        let (width, height) = match self.direction {
+            Direction::Horizontal => (main, cross),
+            Direction::Vertical => (cross, main),
+        };
+        (
+            width.saturating_add(left + right + 2),
+            height.saturating_add(top + bottom + 2),
+        )
+    }
+
+    /// Children are measured at the width they will get, so text that
+    /// wraps makes the block taller.
+    fn height_for(&self, width: u16) -> u16 {
+        let (top, right, bottom, left) = self.styled_border().thickness();
+        let inner = width.saturating_sub(left + right + 2);
+        let children = self.measure_children(Some(inner));
+        let height = match self.direction {
+            Direction::Vertical => self.fit(&children).0,
+            Direction::Horizontal => self
+                .contents
+                .iter()
+                .zip(self.main_sizes(inner, &children))
+                .map(|(el, size)| el.height_for(size))
+                .max()
+                .unwrap_or(0),
+        };
+        height.saturating_add(top + bottom + 2)
+    }
+
+    /// Arranges the children inside the block, one after the other along
+    /// `direction`. Positions are recomputed from scratch on every call.
+    fn arrange(&mut self, rect: Rect) {
+        self.pos = (rect.col, rect.row);
+        self.size = (rect.width, rect.height);
+        let direction = self.direction;
+        let content = self.content_rect();
+        let inner_pos = (content.col, content.row);
+        let (inner_main, inner_cross) = match direction {
+            Direction::Horizontal => (content.width, content.height),
+            Direction::Vertical => (content.height, content.width),
+        };
+
+        let children = self.measure_children(Some(content.width));
+        let sizes = self.main_sizes(inner_main, &children);
+
+        let mut offset: u16 = 0;
+        for (i, (el, child)) in self.contents.iter_mut().zip(&children).enumerate() {
+            if i > 0 {
+                offset = offset
+                    .saturating_add(self.gap)
+                    .saturating_sub(child.overlap);
+            }
+            // Blocks stretch across, widgets keep their own size.
+            let cross = match direction {
+                _ if child.stretch => inner_cross,
+                Direction::Horizontal => cmp::min(el.height_for(sizes[i]), inner_cross),
+                Direction::Vertical => cmp::min(child.cross, inner_cross),
+            };
+            let (child_pos, child_size) = match direction {
+                Direction::Horizontal => (
+                    (inner_pos.0.saturating_add(offset), inner_pos.1),
+                    (sizes[i], cross),
+                ),
+                Direction::Vertical => (
+                    (inner_pos.0, inner_pos.1.saturating_add(offset)),
+                    (cross, sizes[i]),
+                ),
+            };
+            el.arrange(Rect::new(child_pos, child_size));
+            offset = offset.saturating_add(sizes[i]);
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        Rect::new(self.pos, self.size)
+    }
+
+    fn handle_event(&mut self, cx: &mut EventContext) {
+        self.handlers.handle(cx);
+    }
+
+    fn constraint(&self) -> Constraint {
+        self.constraint
+    }
+
+    /// A merging border counts as margin, so it collapses into the border
+    /// of a merging neighbour.
+    fn margins(&self, direction: Direction) -> (u16, u16) {
+        if !self.border.merge {
+            return (0, 0);
+        }
+        let (top, right, bottom, left) = self.styled_border().thickness();
+        match direction {
+            Direction::Horizontal => (left, right),
+            Direction::Vertical => (top, bottom),
+        }
+    }
+
+    fn merges_border(&self) -> bool {
+        self.border.merge
+    }
+
+    fn stretch(&self) -> bool {
+        true
+    }
+
+    fn child_count(&self) -> usize {
+        self.contents.len()
+    }
+
+    fn child(&self, index: usize) -> Option<&dyn Element> {
+        self.contents.get(index).map(|el| &**el as &dyn Element)
+    }
+
+    fn child_mut(&mut self, index: usize) -> Option<&mut dyn Element> {
+        self.contents
+            .get_mut(index)
+            .map(|el| &mut **el as &mut dyn Element)
+    }
+
+    fn type_name(&self) -> &str {
+        "block"
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    fn restyle(&mut self, properties: Properties) {
+        self.sheet = properties;
+    }
+}
+
+impl<'a> Block<'a> {
+    /// The border with what the stylesheet changes about it.
+    fn styled_border(&self) -> Cow<'_, Border> {
+        if self.sheet.border.is_none() && self.sheet.border_color.is_none() {
+            return Cow::Borrowed(&self.border);
+        }
+        let mut border = self.border.clone();
+        border.style = self.sheet.border.unwrap_or(border.style);
+        if let Some(color) = self.sheet.border_color {
+            border.line_style = border.line_style.fg(color);
+        }
+        Cow::Owned(border)
+    }
+
+    /// Inside the border and padding, where children are placed.
+    pub fn content_rect(&self) -> Rect {
+        let (top, right, bottom, left) = self.styled_border().thickness();
+        self.rect().inset(top + 1, right + 1, bottom + 1, left + 1)
+    }
+
+    /// Measures every child along this block's direction. Once the
+    /// `content_width` is known, children of a vertical block are measured
+    /// at the width they will be arranged at.
+    fn measure_children(&self, content_width: Option<u16>) -> Vec<ChildMeasure> {
+        let direction = self.direction;
+        let mut prev_trailing = None;
+        self.contents
+            .iter()
+            .map(|el| {
+                let (width, mut height) = el.measure();
+                if let (Direction::Vertical, Some(max)) = (direction, content_width) {
+                    let arranged = if el.stretch() {
+                        max
+                    } else {
+                        cmp::min(width, max)
+                    };
+                    height = el.height_for(arranged);
+                }
+                let (leading, trailing) = el.margins(direction);
+                let merges = el.merges_border();
+                let overlap = match prev_trailing {
+                    Some((prev, prev_merges)) if prev_merges == merges => cmp::min(prev, leading),
+                    _ => 0,
+                };
+                prev_trailing = Some((trailing, merges));
+                let (main, cross) = match direction {
+                    Direction::Horizontal => (width, height),
+                    Direction::Vertical => (height, width),
+                };
+                ChildMeasure {
+                    constraint: el.constraint(),
+                    main,
+                    cross,
+                    overlap,
+                    stretch: el.stretch(),
+                }
+            })
+            .collect()
+    }
+
+    /// The main and cross size the children take without being squeezed.
+    fn fit(&self, children: &[ChildMeasure]) -> (u16, u16) {
+        let mut main: u16 = 0;
+        let mut cross: u16 = 0;
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 {
+                main = main.saturating_add(self.gap).saturating_sub(child.overlap);
+            }
+            let size = match child.constraint {
+                Constraint::Fixed(n) => n,
+                constraint => constraint.base(0, child.main),
+            };
+            main = main.saturating_add(size);
+            cross = cmp::max(cross, child.cross);
+        }
+        (main, cross)
+    }
+
+    /// Splits `inner_main` between the children along the block's
+    /// direction.
+    fn main_sizes(&self, inner_main: u16, children: &[ChildMeasure]) -> Vec<u16> {
+        let gaps = self
+            .gap
+            .saturating_mul(children.len().saturating_sub(1) as u16);
+        let overlap = children
+            .iter()
+            .fold(0, |acc: u16, c| acc.saturating_add(c.overlap));
+        let available = inner_main.saturating_add(overlap).saturating_sub(gaps);
+        let items: Vec<_> = children.iter().map(|c| (c.constraint, c.main)).collect();
+        distribute(available, &items)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            // parent: None,
+            pos: (0, 0),
+            size: (4, 4),
+            contents: vec![],
+            direction: Direction::Horizontal,
+            gap: 0,
+            constraint: Constraint::Auto,
+            border: Border::default(),
+            style: Style::default(),
+            id: None,
+            classes: vec![],
+            sheet: Properties::default(),
+            handlers: Handlers::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Adds a class for stylesheets to select the block by.
+    pub fn class<S: Into<String>>(mut self, class: S) -> Self {
+        self.classes.push(class.into());
+        self
+    }
+
+    /// Changes the border, e.g.
+    /// `.border(|b| b.style(BorderStyle::Rounded).title(" Files "))`.
+    pub fn border<F: FnOnce(Border) -> Border>(mut self, f: F) -> Self {
+        self.border = f(self.border);
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn gap(mut self, gap: u16) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Runs `f` for left clicks on the block that its children let through.
+    /// The click count is in `cx.event`.
+    pub fn on_click<F: FnMut(&mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_click = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` when the pointer moves onto the block itself.
+    pub fn on_hover<F: FnMut(&mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_hover = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` when the pointer moves off the block itself.
+    pub fn on_mouse_leave<F: FnMut(&mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_mouse_leave = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` for key presses that its focused descendant lets through.
+    pub fn on_key<F: FnMut(&KeyEvent, &mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_key = Some(Box::new(f));
+        self
+    }
+
+    /// Appends a child. It gets its position the next time the tree is
+    /// arranged.
+    pub fn push<E: Element + 'a>(&mut self, element: E) -> &mut (dyn Element + 'a) {
+        self.contents.push(Box::new(element));
+        let last = self.contents.len() - 1;
+        &mut *self.contents[last]
+    }
+
+    pub fn remove(&mut self, index: usize) -> Box<dyn Element + 'a> {
+        self.contents.remove(index)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&(dyn Element + 'a)> {
+        self.contents.get(index).map(|el| &**el)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut (dyn Element + 'a)> {
+        self.contents.get_mut(index).map(|el| &mut **el)
+    }
+
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+}
+
+impl Default for Block<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A child's size along and across its parent's direction.
+struct ChildMeasure {
+    constraint: Constraint,
+    main: u16,
+    cross: u16,
+    /// Margin shared with the previous sibling.
+    overlap: u16,
+    stretch: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Area {
+    pub top: u16,
+    pub right: u16,
+    pub bottom: u16,
+    pub left: u16,
+    pub symbol: char,
+    /// Laid over the theme's surface style.
+    pub style: Style,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AreaShort {
+    Uniform(u16),
+    HorVer(u16, u16),
+    All(u16, u16, u16, u16),
+}
+
+impl Area {
+    pub fn set(mut self, short: AreaShort) -> Self {
+        (self.top, self.right, self.bottom, self.left) = match short {
+            AreaShort::Uniform(uni) => (uni, uni, uni, uni),
+            AreaShort::HorVer(hor, ver) => (hor, ver, hor, ver),
+            AreaShort::All(t, r, b, l) => (t, r, b, l),
+        };
+        self
+    }
+
+    pub fn symbol(mut self, symbol: char) -> Self {
+        self.symbol = symbol;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.style = self.style.fg(color);
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Self {
+        self.style = self.style.bg(background);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl Default for Area {
+    fn default() -> Self {
+        Self {
+            top: 1,
+            right: 1,
+            bottom: 1,
+            left: 1,
+            symbol: ' ',
+            style: Style::default(),
+        }
+    }
+}
+
+pub struct Widget<'a> {
+    pub text: Text,
+    /// Laid over the theme's style for the widget's state.
+    pub style: Style,
+    /// Disabled widgets ignore input and cannot be focused.
+    pub disabled: bool,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    /// Set by the stylesheet.
+    sheet: Properties,
+    pub padding: Area,
+    pub margin: Area,
+    // Including margin & padding!
+    pub size: (u16, u16),
+    pub pos: (u16, u16),
+    pub constraint: Constraint,
+    pub focusable: bool,
+    pub wrap: Wrap,
+    pub align: Align,
+    pub vertical_align: VerticalAlign,
+    pub overflow: Overflow,
+    /// The first line shown with `Overflow::Scroll`.
+    pub scroll: u16,
+    handlers: Handlers<'a>,
+    on_scroll: Option<ScrollCallback<'a>>,
+}
+
+impl<'a> Widget<'a> {
+    pub fn new<T: Into<Text>>(text: T) -> Self {
+        Widget {
+            text: text.into(),
+            style: Style::default(),
+            disabled: false,
+            id: None,
+            classes: vec![],
+            sheet: Properties::default(),
+            pos: (0, 0),
+            padding: Area::default().symbol('$'),
+            size: (3, 3),
+            margin: Area::default().symbol('#'),
+            constraint: Constraint::Auto,
+            focusable: true,
+            wrap: Wrap::default(),
+            align: Align::default(),
+            vertical_align: VerticalAlign::default(),
+            overflow: Overflow::default(),
+            scroll: 0,
+            handlers: Handlers::default(),
+            on_scroll: None,
+        }
+    }
+
+    pub fn constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    pub fn focusable(mut self, focusable: bool) -> Self {
+        self.focusable = focusable;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+        self
+    }
+
+    pub fn id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Adds a class for stylesheets to select the widget by.
+    pub fn class<S: Into<String>>(mut self, class: S) -> Self {
+        self.classes.push(class.into());
+        self
+    }
+
+    /// The margin with the sizes the stylesheet sets.
+    fn margin_area(&self) -> Area {
+        match self.sheet.margin {
+            Some(short) => self.margin.set(short),
+            None => self.margin,
+        }
+    }
+
+    /// The padding with the sizes the stylesheet sets.
+    fn padding_area(&self) -> Area {
+        match self.sheet.padding {
+            Some(short) => self.padding.set(short),
+            None => self.padding,
+        }
+    }
+
+    pub fn wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn vertical_align(mut self, vertical_align: VerticalAlign) -> Self {
+        self.vertical_align = vertical_align;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn scroll(mut self, scroll: u16) -> Self {
+        self.scroll = scroll;
+        self
+    }
+
+    /// Runs `f` with the new first line when the wheel scrolls the widget.
+    /// A widget rebuilt every frame, as under `App`, starts from `scroll`
+    /// again, so keep the offset in state and pass it back with `scroll`.
+    pub fn on_scroll<F: FnMut(u16, &mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.on_scroll = Some(Box::new(f));
+        self
+    }
+
+    /// Inside the margin and padding, where the text goes.
+    pub fn text_rect(&self) -> Rect {
+        let (margin, padding) = (self.margin_area(), self.padding_area());
+        self.rect()
+            .inset(margin.top, margin.right, margin.bottom, margin.left)
+            .inset(padding.top, padding.right, padding.bottom, padding.left)
+    }
+
+    /// The text broken into lines for the current width.
+    fn lines(&self) -> Vec<WrappedLine> {
+        text::wrap(&self.text, self.text_rect().width, self.wrap)
+    }
+
+    /// How far the text can scroll before its last line reaches the bottom.
+    fn max_scroll(&self) -> u16 {
+        let lines = self.lines().len() as u16;
+        lines.saturating_sub(self.text_rect().height)
+    }
+
+    /// Runs `f` when the widget is clicked, or activated with Enter or Space.
+    /// The click count is in `cx.event`. Widgets without one let clicks and
+    /// those keys through to their parent.
+    pub fn on_click<F: FnMut(&mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_click = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` when the pointer moves onto this widget.
+    pub fn on_hover<F: FnMut(&mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_hover = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` when the pointer moves off this widget.
+    pub fn on_mouse_leave<F: FnMut(&mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_mouse_leave = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` for key presses while the widget has focus.
+    pub fn on_key<F: FnMut(&KeyEvent, &mut EventContext) + 'a>(mut self, f: F) -> Self {
+        self.handlers.on_key = Some(Box::new(f));
+        self
+    }
+
+    pub fn padding<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Area) -> Area,
+    {
+        self.padding = f(self.padding);
+        self
+    }
+
+    pub fn margin<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Area) -> Area,
+    {
+        self.margin = f(self.margin);
+        self
+    }
+}
+
+impl Element for Widget<'_> {
+    fn render(&self, canvas: &mut Canvas) {
+        let mut canvas = canvas.clipped(self.rect());
+        let theme = canvas.theme();
+        let mut style = theme.primary;
+        if self.disabled {
+            style = style.patch(theme.disabled);
+        } else {
+            if canvas.is_hovered() {
+                style = style.patch(theme.hovered);
+            }
+            if canvas.is_pressed() {
+                style = style.patch(theme.pressed);
+            }
+        }
+        let style = style.patch(self.style).patch(self.sheet.style);
+        let (margin, padding) = (self.margin_area(), self.padding_area());
+
+        // margin
+        // TODO: make symbol a string and only accept char to set symbol?
+        let rect = self.rect();
+        canvas.fill(
+            rect,
+            Pixel::styled(margin.symbol, theme.surface.patch(margin.style)),
+        );
+        // padding
+        let rect = rect.inset(margin.top, margin.right, margin.bottom, margin.left);
+        canvas.fill(
+            rect,
+            Pixel::styled(padding.symbol, theme.surface.patch(padding.style)),
+        );
+
+        let rect = self.text_rect();
+        let focused = canvas.is_focused();
+        let mut canvas = canvas.clipped(rect);
+        canvas.fill(rect, Pixel::styled(' ', style));
+
+        let lines = self.lines();
+        let first = match self.overflow {
+            Overflow::Scroll => cmp::min(self.scroll, self.max_scroll()),
+            _ => 0,
+        };
+        let shown: Vec<_> = lines
+            .iter()
+            .skip(first.into())
+            .take(rect.height.into())
+            .collect();
+        let free = rect.height.saturating_sub(shown.len() as u16);
+        let top = match self.sheet.vertical_align.unwrap_or(self.vertical_align) {
+            VerticalAlign::Top => 0,
+            VerticalAlign::Middle => free / 2,
+            VerticalAlign::Bottom => free,
+        };
+        let align = self.sheet.align.unwrap_or(self.align);
+        let cut_below = usize::from(first) + shown.len() < lines.len();
+        let base = Pixel::styled(' ', style);
+        for (i, line) in shown.iter().enumerate() {
+            let (offset, mut line) = text::align(line, rect.width, align);
+            if self.overflow == Overflow::Ellipsis {
+                let last = i + 1 == shown.len();
+                line = text::ellipsize(&line, rect.width, last && cut_below);
+            }
+            let row = rect.row + top + i as u16;
+            let mut col = rect.col + offset;
+            for span in &line.spans {
+                let mut px = base.clone();
+                span.style.apply(&mut px);
+                col = canvas.print_styled(col, row, &span.content, &px);
+            }
+        }
+        if focused {
+            canvas.update(rect, |px| theme.focused.apply(px));
+        }
+    }
+
+    /// Size including margin and padding. The text is measured unwrapped,
+    /// one line per newline; `height_for` accounts for wrapping once the
+    /// width is known.
+    fn measure(&self) -> (u16, u16) {
+        let (margin, padding) = (self.margin_area(), self.padding_area());
+        let (text_width, text_height) = (self.text.width(), self.text.height());
+        let sum = |sizes: [u16; 5]| sizes.into_iter().fold(0, u16::saturating_add);
+        (
+            sum([
+                margin.left,
+                margin.right,
+                padding.left,
+                padding.right,
+                text_width,
+            ]),
+            sum([
+                margin.top,
+                margin.bottom,
+                padding.top,
+                padding.bottom,
+                text_height,
+            ]),
+        )
+    }
+
+    fn height_for(&self, width: u16) -> u16 {
+        if self.wrap == Wrap::None {
+            return self.measure().1;
+        }
+        let (margin, padding) = (self.margin_area(), self.padding_area());
+        let text_width = [margin.left, margin.right, padding.left, padding.right]
+            .into_iter()
+            .fold(width, u16::saturating_sub);
+        let lines = text::wrap(&self.text, text_width, self.wrap).len();
+        [margin.top, margin.bottom, padding.top, padding.bottom]
+            .into_iter()
+            .fold(
+                u16::try_from(lines).unwrap_or(u16::MAX),
+                u16::saturating_add,
+            )
+    }
+
+    fn arrange(&mut self, rect: Rect) {
+        self.pos = (rect.col, rect.row);
+        self.size = (rect.width, rect.height);
+    }
+
+    fn rect(&self) -> Rect {
+        Rect::new(self.pos, self.size)
+    }
+
+    /// The margin is not part of the widget.
+    fn hit(&self, pos: (u16, u16)) -> bool {
+        let margin = self.margin_area();
+        self.rect()
+            .inset(margin.top, margin.right, margin.bottom, margin.left)
+            .contains(pos)
+    }
+
+    fn handle_event(&mut self, cx: &mut EventContext) {
+        if cx.phase != Phase::Target || self.disabled {
+            return;
+        }
+        match &cx.event {
+            UiEvent::Key(key)
+                if matches!(key.code, KeyCode::Enter | KeyCode::Char(' '))
+                    && self.handlers.on_click.is_some() =>
+            {
+                self.handlers.click(cx);
+                cx.stop_propagation();
+            }
+            UiEvent::Click {
+                button: MouseButton::Left,
+                ..
+            } if self.handlers.on_click.is_some() => {
+                self.handlers.handle(cx);
+                cx.stop_propagation();
+            }
+            UiEvent::Mouse(mouse) if self.overflow == Overflow::Scroll => {
+                let current = cmp::min(self.scroll, self.max_scroll());
+                let scroll = match mouse.kind {
+                    MouseEventKind::ScrollUp => current.saturating_sub(1),
+                    MouseEventKind::ScrollDown => current.saturating_add(1),
+                    _ => return self.handlers.handle(cx),
+                };
+                let scroll = cmp::min(scroll, self.max_scroll());
+                if scroll != self.scroll {
+                    self.scroll = scroll;
+                    if let Some(f) = &mut self.on_scroll {
+                        f(scroll, cx);
+                    }
+                    cx.request_redraw();
+                }
+                cx.stop_propagation();
+            }
+            _ => self.handlers.handle(cx),
+        }
+    }
+
+    fn constraint(&self) -> Constraint {
+        self.constraint
+    }
+
+    fn margins(&self, direction: Direction) -> (u16, u16) {
+        let margin = self.margin_area();
+        match direction {
+            Direction::Horizontal => (margin.left, margin.right),
+            Direction::Vertical => (margin.top, margin.bottom),
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        self.focusable && !self.disabled
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.constraint.validate()?;
+        for (name, area) in [("margin", &self.margin), ("padding", &self.padding)] {
+            if display_width(area.symbol.encode_utf8(&mut [0; 4])) != 1 {
+                return Err(Error::InvalidConfig(format!(
+                    "{name} symbol {:?} is not one column wide",
+                    area.symbol
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn type_name(&self) -> &str {
+        "widget"
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    fn restyle(&mut self, properties: Properties) {
+        self.sheet = properties;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        border::{BorderStyle, Sides, Title, TitlePosition},
+        event::ScrollDirection,
+    };
+    use crossterm::event::MouseEvent;
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
+
+    #[test]
+    fn empty_block_renders_border() {
+        let mut ctx = Context::with_size((4, 4));
+        let mut ui = UI::new(TestBackend::new(4, 4), Block::new());
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+####
+#  #
+#  #
+####",
+        );
+    }
+
+    #[test]
+    fn widget_draws_inside_block() {
+        let mut ctx = Context::with_size((10, 9));
+        let mut ui = UI::new(TestBackend::new(10, 9), Block::new());
+        ui.root.push(Widget::new("hi"));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+##########
+#        #
+# ###### #
+# #$$$$# #
+# #$hi$# #
+# #$$$$# #
+# ###### #
+#        #
+##########",
+        );
+    }
+
+    #[test]
+    fn pen_emits_style_changes_between_cells() {
+        let mut backend = TestBackend::new(2, 1);
+        let bold_dim = Attributes::from(&[Attribute::Bold, Attribute::Dim][..]);
+        let pixels = [
+            Pixel::new('a', Color::Red, Color::Blue)
+                .attributes(bold_dim)
+                .underline_color(Color::Green),
+            Pixel::new('b', Color::Red, Color::Blue).attributes(Attribute::Dim.into()),
+        ];
+        let mut pen = Pen::default();
+        for px in &pixels {
+            pen.apply(&mut backend, px).unwrap();
+            backend.print(&px.symbol).unwrap();
+        }
+
+        let cells = backend.cells();
+        assert_eq!(cells[0][0].attributes, bold_dim);
+        assert_eq!(cells[0][0].underline_color, Some(Color::Green));
+        assert_eq!(cells[0][1].background, Color::Blue);
+        assert_eq!(cells[0][1].attributes, Attribute::Dim.into());
+        assert_eq!(cells[0][1].underline_color, None);
+    }
+
+    /// Wraps a `TestBackend` and counts the commands sent to it.
+    struct CountingBackend {
+        inner: TestBackend,
+        moves: usize,
+        prints: usize,
+    }
+
+    impl Backend for CountingBackend {
+        fn move_to(&mut self, col: u16, row: u16) -> io::Result<()> {
+            self.moves += 1;
+            self.inner.move_to(col, row)
+        }
+        fn print(&mut self, text: &str) -> io::Result<()> {
+            self.prints += 1;
+            self.inner.print(text)
+        }
+        fn set_foreground(&mut self, color: Color) -> io::Result<()> {
+            self.inner.set_foreground(color)
+        }
+        fn set_background(&mut self, color: Color) -> io::Result<()> {
+            self.inner.set_background(color)
+        }
+        fn set_attribute(&mut self, attribute: Attribute) -> io::Result<()> {
+            self.inner.set_attribute(attribute)
+        }
+        fn set_underline_color(&mut self, color: Color) -> io::Result<()> {
+            self.inner.set_underline_color(color)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+        fn size(&self) -> io::Result<(u16, u16)> {
+            self.inner.size()
+        }
+        fn clear(&mut self) -> io::Result<()> {
+            self.inner.clear()
+        }
+        fn cursor_position(&mut self) -> io::Result<(u16, u16)> {
+            self.inner.cursor_position()
+        }
+        fn enter_alternate_screen(&mut self) -> io::Result<()> {
+            self.inner.enter_alternate_screen()
+        }
+        fn leave_alternate_screen(&mut self) -> io::Result<()> {
+            self.inner.leave_alternate_screen()
+        }
+    }
+
+    #[test]
+    fn unchanged_frame_emits_nothing() {
+        let mut ctx = Context::with_size((40, 20));
+        let mut ui = UI::new(
+            CountingBackend {
+                inner: TestBackend::new(40, 20),
+                moves: 0,
+                prints: 0,
+            },
+            Block::new(),
+        );
+        ui.root.push(Widget::new("hi"));
+        ui.render(&mut ctx).unwrap();
+        // One run per row, since the whole screen is painted the first time.
+        assert_eq!(ui.backend.moves, 20);
+
+        ui.backend.moves = 0;
+        ui.backend.prints = 0;
+        ui.render(&mut ctx).unwrap();
+        assert_eq!((ui.backend.moves, ui.backend.prints), (0, 0));
+    }
+
+    #[test]
+    fn wide_graphemes_take_two_cells() {
+        let mut ui = UI::new(TestBackend::new(13, 9), Block::new());
+        let mut ctx = Context::with_size((13, 9));
+        ui.root.push(Widget::new("日本e\u{301}"));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+#############
+#           #
+# ######### #
+# #$$$$$$$# #
+# #$日本e\u{301}$# #
+# #$$$$$$$# #
+# ######### #
+#           #
+#############",
+        );
+        let cells = ui.backend.cells();
+        assert_eq!(cells[4][4].symbol, "日");
+        assert_eq!(cells[4][5].symbol, "");
+        assert_eq!(cells[4][8].symbol, "e\u{301}");
+    }
+
+    #[test]
+    fn vertical_block_honors_constraints_and_gap() {
+        let mut ui = UI::new(
+            TestBackend::new(12, 16),
+            Block::new().direction(Direction::Vertical).gap(1),
+        );
+        let mut ctx = Context::with_size((12, 16));
+        ui.root.push(Block::new().constraint(Constraint::Fixed(4)));
+        ui.root.push(Block::new().constraint(Constraint::Fill(1)));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+############
+#          #
+# ######## #
+# #      # #
+# #      # #
+# ######## #
+#          #
+# ######## #
+# #      # #
+# #      # #
+# #      # #
+# #      # #
+# #      # #
+# ######## #
+#          #
+############",
+        );
+    }
+
+    #[test]
+    fn arrange_is_independent_of_push_order() {
+        let mut root = Block::new();
+        root.push(Widget::new("a"));
+        root.arrange(Rect::new((0, 0), (30, 10)));
+        root.arrange(Rect::new((0, 0), (30, 10)));
+        root.push(Widget::new("b"));
+        root.remove(0);
+        root.arrange(Rect::new((0, 0), (30, 10)));
+
+        assert_eq!(root.get(0).unwrap().rect(), Rect::new((2, 2), (5, 5)));
+    }
+
+    #[test]
+    fn overflowing_children_are_clipped() {
+        let mut ui = UI::new(TestBackend::new(9, 7), Block::new());
+        let mut ctx = Context::with_size((9, 7));
+        ui.root
+            .push(Widget::new("overflowing").margin(|m| m.set(AreaShort::Uniform(0))));
+        ui.root.push(Block::new());
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+#########
+#       #
+# $$$$$ #
+# $over #
+# $$$$$ #
+#       #
+#########",
+        );
+    }
+
+    #[test]
+    fn resize_event_reflows_layout() {
+        let mut ui = UI::new(TestBackend::new(4, 4), Block::new());
+        let mut ctx = Context::with_size((4, 4));
+        ui.render(&mut ctx).unwrap();
+
+        ctx.process(&Event::Resize(6, 5));
+        assert_eq!(ctx.max, (4, 4));
+        assert!(ctx.resize_timeout().is_some());
+
+        ctx.resize_debounce = Duration::ZERO;
+        ctx.process(&Event::Resize(6, 5));
+        ui.backend.resize(6, 5);
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+######
+#    #
+#    #
+#    #
+######",
+        );
+    }
+
+    #[test]
+    fn custom_elements_live_in_blocks() {
+        struct Dot(Rect);
+
+        impl Element for Dot {
+            fn measure(&self) -> (u16, u16) {
+                (1, 1)
+            }
+            fn arrange(&mut self, rect: Rect) {
+                self.0 = rect;
+            }
+            fn rect(&self) -> Rect {
+                self.0
+            }
+            fn render(&self, canvas: &mut Canvas) {
+                canvas.put(
+                    self.0.col,
+                    self.0.row,
+                    Pixel::new('o', Color::Reset, Color::Reset),
+                );
+            }
+        }
+
+        let mut ui = UI::new(TestBackend::new(6, 5), Block::new());
+        let mut ctx = Context::with_size((6, 5));
+        ui.root.push(Dot(Rect::default()));
+        ui.root.push(Dot(Rect::default()));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+######
+#    #
+# oo #
+#    #
+######",
+        );
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn mouse(kind: MouseEventKind, column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    #[test]
+    fn tab_cycles_focus_in_tree_order() {
+        let mut ui = UI::new(
+            TestBackend::new(30, 20),
+            Block::new().direction(Direction::Vertical),
+        );
+        let mut ctx = Context::with_size((30, 20));
+        let mut row = Block::new();
+        row.push(Widget::new("a"));
+        row.push(Widget::new("b").focusable(false));
+        row.push(Widget::new("c"));
+        ui.root.push(row);
+        ui.root.push(Widget::new("d"));
+        ui.render(&mut ctx).unwrap();
+
+        let mut seen = vec![];
+        for _ in 0..4 {
+            ctx.process(&key(KeyCode::Tab));
+            ui.process(&mut ctx).unwrap();
+            seen.push(ctx.focus.focused().unwrap().to_vec());
+        }
+        assert_eq!(seen, vec![vec![0, 0], vec![0, 2], vec![1], vec![0, 0]]);
+
+        ctx.process(&key(KeyCode::BackTab));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(ctx.focus.focused(), Some(&[1][..]));
+
+        ui.render(&mut ctx).unwrap();
+        let text = ui.backend.cells()[13][4].clone();
+        assert_eq!(text.symbol, "d");
+        assert!(text.attributes.has(Attribute::Reverse));
+    }
+
+    #[test]
+    fn arrows_move_focus_spatially() {
+        let mut ui = UI::new(
+            TestBackend::new(30, 20),
+            Block::new().direction(Direction::Vertical),
+        );
+        let mut ctx = Context::with_size((30, 20));
+        let mut row = Block::new();
+        row.push(Widget::new("a"));
+        row.push(Widget::new("b"));
+        ui.root.push(row);
+        ui.root.push(Widget::new("c"));
+        ui.render(&mut ctx).unwrap();
+
+        ctx.focus.focus(&[1]);
+        for (code, expected) in [
+            (KeyCode::Up, [0, 0]),
+            (KeyCode::Right, [0, 1]),
+            (KeyCode::Left, [0, 0]),
+        ] {
+            ctx.process(&key(code));
+            ui.process(&mut ctx).unwrap();
+            assert_eq!(ctx.focus.focused(), Some(&expected[..]));
+        }
+    }
+
+    /// Wraps a block and swallows key events in one phase.
+    struct Catcher<'a> {
+        inner: Block<'a>,
+        phase: Phase,
+        caught: Rc<Cell<Option<KeyCode>>>,
+    }
+
+    impl Element for Catcher<'_> {
+        fn measure(&self) -> (u16, u16) {
+            self.inner.measure()
+        }
+        fn arrange(&mut self, rect: Rect) {
+            self.inner.arrange(rect)
+        }
+        fn rect(&self) -> Rect {
+            self.inner.rect()
+        }
+        fn render(&self, canvas: &mut Canvas) {
+            self.inner.render(canvas)
+        }
+        fn handle_event(&mut self, cx: &mut EventContext) {
+            if let (UiEvent::Key(key), true) = (&cx.event, cx.phase == self.phase) {
+                self.caught.set(Some(key.code));
+                cx.stop_propagation();
+            }
+        }
+        fn child_count(&self) -> usize {
+            self.inner.child_count()
+        }
+        fn child(&self, index: usize) -> Option<&dyn Element> {
+            self.inner.child(index)
+        }
+        fn child_mut(&mut self, index: usize) -> Option<&mut dyn Element> {
+            self.inner.child_mut(index)
+        }
+    }
+
+    /// A catcher around one widget, with the widget focused.
+    fn focused_catcher(
+        phase: Phase,
+        widget: Widget<'static>,
+    ) -> (UI<'static, TestBackend>, Context, Rc<Cell<Option<KeyCode>>>) {
+        let caught = Rc::new(Cell::new(None));
+        let mut inner = Block::new();
+        inner.push(widget);
+        let mut ui = UI::new(TestBackend::new(20, 10), Block::new());
+        ui.root.push(Catcher {
+            inner,
+            phase,
+            caught: caught.clone(),
+        });
+        let mut ctx = Context::with_size((20, 10));
+        ui.render(&mut ctx).unwrap();
+        ctx.process(&key(KeyCode::Tab));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(ctx.focus.focused(), Some(&[0, 0][..]));
+        (ui, ctx, caught)
+    }
+
+    #[test]
+    fn unhandled_keys_bubble_to_parents() {
+        let (mut ui, mut ctx, caught) = focused_catcher(Phase::Bubble, Widget::new("a"));
+        ctx.process(&key(KeyCode::Char('x')));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(caught.get(), Some(KeyCode::Char('x')));
+        // Without a click handler, Enter is left for the parent.
+        ctx.process(&key(KeyCode::Enter));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(caught.get(), Some(KeyCode::Enter));
+
+        let clicks = Rc::new(Cell::new(0));
+        let counter = clicks.clone();
+        let button = Widget::new("a").on_click(move |_| counter.set(counter.get() + 1));
+        let (mut ui, mut ctx, caught) = focused_catcher(Phase::Bubble, button);
+        ctx.process(&key(KeyCode::Enter));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!((clicks.get(), caught.get()), (1, None));
+    }
+
+    #[test]
+    fn capture_phase_sees_keys_before_the_target() {
+        let (mut ui, mut ctx, caught) = focused_catcher(Phase::Capture, Widget::new("a"));
+        ctx.process(&key(KeyCode::Enter));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(caught.get(), Some(KeyCode::Enter));
+        // Stopped keys get no default action either.
+        ctx.process(&key(KeyCode::Tab));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(ctx.focus.focused(), Some(&[0, 0][..]));
+    }
+
+    #[test]
+    fn clicks_only_reach_the_topmost_element() {
+        /// Sits at a fixed spot regardless of layout and counts clicks.
+        struct Pad(Rect, Rc<Cell<u32>>);
+
+        impl Element for Pad {
+            fn measure(&self) -> (u16, u16) {
+                (1, 1)
+            }
+            fn arrange(&mut self, _rect: Rect) {}
+            fn rect(&self) -> Rect {
+                self.0
+            }
+            fn render(&self, _canvas: &mut Canvas) {}
+            fn handle_event(&mut self, cx: &mut EventContext) {
+                if let UiEvent::Mouse(_) = cx.event {
+                    self.1.set(self.1.get() + 1);
+                    cx.stop_propagation();
+                }
+            }
+        }
+
+        let below = Rc::new(Cell::new(0));
+        let above = Rc::new(Cell::new(0));
+        let spot = Rect::new((2, 2), (3, 1));
+        let mut ui = UI::new(TestBackend::new(10, 5), Block::new());
+        ui.root.push(Pad(spot, below.clone()));
+        ui.root.push(Pad(spot, above.clone()));
+        let mut ctx = Context::with_size((10, 5));
+        ui.render(&mut ctx).unwrap();
+
+        ctx.process(&mouse(MouseEventKind::Down(MouseButton::Left), 3, 2));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!((below.get(), above.get()), (0, 1));
+        assert_eq!(ctx.pressed, Some(vec![1]));
+    }
+
+    #[test]
+    fn callbacks_change_state_and_request_redraws() {
+        let clicks = Cell::new(0);
+        let hovering = Cell::new(false);
+        let quit = Cell::new(false);
+        let mut ui = UI::new(
+            TestBackend::new(20, 10),
+            Block::new().on_key(|key, cx| {
+                if key.code == KeyCode::Char('q') {
+                    quit.set(true);
+                    cx.stop_propagation();
+                }
+            }),
+        );
+        ui.root.push(
+            Widget::new("ok")
+                .on_click(|cx| {
+                    clicks.set(clicks.get() + 1);
+                    cx.request_redraw();
+                })
+                .on_hover(|_| hovering.set(true))
+                .on_mouse_leave(|_| hovering.set(false)),
+        );
+        let mut ctx = Context::with_size((20, 10));
+        ui.render(&mut ctx).unwrap();
+        assert!(!ctx.needs_redraw());
+
+        let rect = ui.root.get(0).unwrap().rect();
+        let (col, row) = (rect.col + rect.width / 2, rect.row + rect.height / 2);
+        ctx.process(&mouse(MouseEventKind::Moved, col, row));
+        ui.process(&mut ctx).unwrap();
+        assert!(hovering.get());
+        ctx.process(&mouse(MouseEventKind::Down(MouseButton::Left), col, row));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(clicks.get(), 0);
+        ctx.process(&mouse(MouseEventKind::Up(MouseButton::Left), col, row));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(clicks.get(), 1);
+        assert!(ctx.needs_redraw());
+        ctx.process(&mouse(MouseEventKind::Moved, 0, 0));
+        ui.process(&mut ctx).unwrap();
+        assert!(!hovering.get());
+
+        // The widget has focus from the click; Space activates it too and
+        // other keys bubble up to the root.
+        ctx.process(&key(KeyCode::Char(' ')));
+        ui.process(&mut ctx).unwrap();
+        assert_eq!(clicks.get(), 2);
+        ctx.process(&key(KeyCode::Char('q')));
+        ui.process(&mut ctx).unwrap();
+        assert!(quit.get());
+    }
+
+    #[test]
+    fn clicks_fire_on_release_over_the_pressed_widget() {
+        let counts = RefCell::new(vec![]);
+        let mut ui = UI::new(TestBackend::new(20, 10), Block::new());
+        ui.root.push(Widget::new("ok").on_click(|cx| {
+            if let UiEvent::Click { count, .. } = cx.event {
+                counts.borrow_mut().push(count);
+            }
+        }));
+        let mut ctx = Context::with_size((20, 10));
+        ctx.mouse.multi_click_interval = Duration::from_secs(60);
+        ui.render(&mut ctx).unwrap();
+        let rect = ui.root.get(0).unwrap().rect();
+        let (col, row) = (rect.col + rect.width / 2, rect.row + rect.height / 2);
+
+        let mut press = |ctx: &mut Context, kind, col, row| {
+            ctx.process(&mouse(kind, col, row));
+            ui.process(ctx).unwrap();
+        };
+        for _ in 0..3 {
+            press(&mut ctx, MouseEventKind::Down(MouseButton::Left), col, row);
+            assert!(ctx.mouse.is_down(MouseButton::Left));
+            press(&mut ctx, MouseEventKind::Up(MouseButton::Left), col, row);
+        }
+        // Dragging off the widget before letting go cancels the click.
+        press(&mut ctx, MouseEventKind::Down(MouseButton::Left), col, row);
+        press(&mut ctx, MouseEventKind::Drag(MouseButton::Left), 0, 0);
+        press(&mut ctx, MouseEventKind::Up(MouseButton::Left), 0, 0);
+        // The right button does not trigger on_click.
+        press(&mut ctx, MouseEventKind::Down(MouseButton::Right), col, row);
+        press(&mut ctx, MouseEventKind::Up(MouseButton::Right), col, row);
+        assert_eq!(*counts.borrow(), vec![1, 2, 3]);
+        assert!(ctx.mouse.buttons.is_empty());
+        assert_eq!(ctx.mouse.click_count, 1);
+
+        press(&mut ctx, MouseEventKind::ScrollUp, col, row);
+        assert_eq!(ctx.mouse.scroll, Some(ScrollDirection::Up));
+        assert_eq!(ctx.mouse.position, Some((col, row)));
+    }
+
+    #[test]
+    fn clicks_bubble_past_widgets_without_a_handler() {
+        let clicks = Rc::new(Cell::new(0));
+        let counter = clicks.clone();
+        let mut inner = Block::new().on_click(move |_| counter.set(counter.get() + 1));
+        inner.push(Widget::new("ok"));
+        inner.push(Widget::new("no").on_click(|_| {}));
+        let mut ui = UI::new(TestBackend::new(30, 15), Block::new());
+        ui.root.push(inner);
+        let mut ctx = Context::with_size((30, 15));
+        ui.render(&mut ctx).unwrap();
+
+        for i in 0..2 {
+            let rect = ui.root.get(0).unwrap().child(i).unwrap().rect();
+            let (col, row) = (rect.col + rect.width / 2, rect.row + rect.height / 2);
+            for kind in [
+                MouseEventKind::Down(MouseButton::Left),
+                MouseEventKind::Up(MouseButton::Left),
+            ] {
+                ctx.process(&mouse(kind, col, row));
+                ui.process(&mut ctx).unwrap();
+            }
+        }
+        // Only the click on the plain widget reaches the block.
+        assert_eq!(clicks.get(), 1);
+    }
+
+    #[test]
+    fn widgets_resolve_their_style_from_the_theme() {
+        let clicked = Cell::new(false);
+        let mut ui = UI::new(TestBackend::new(20, 10), Block::new());
+        ui.root
+            .push(Widget::new("a").style(Style::new().fg(Color::Yellow)));
+        ui.root.push(
+            Widget::new("b")
+                .disabled(true)
+                .on_click(|_| clicked.set(true)),
+        );
+        let mut ctx = Context::with_size((20, 10));
+        ctx.theme = Theme {
+            primary: Style::new().fg(Color::Green).bg(Color::Black),
+            hovered: Style::new().bg(Color::Blue),
+            ..Theme::default()
+        };
+        ui.render(&mut ctx).unwrap();
+        let text = |ui: &UI<TestBackend>, i| {
+            let rect = ui.root.get(i).unwrap().rect();
+            (rect.col + 2, rect.row + 2)
+        };
+        let (a, b) = (text(&ui, 0), text(&ui, 1));
+        let cell = |ui: &UI<TestBackend>, (col, row): (u16, u16)| {
+            ui.backend.cells()[row as usize][col as usize].clone()
+        };
+        assert_eq!(cell(&ui, a).color, Color::Yellow);
+        assert_eq!(cell(&ui, a).background, Color::Black);
+        assert_eq!(cell(&ui, b).color, Color::DarkGrey);
+
+        // Hovering changes the background, but not of a disabled widget.
+        for (col, row) in [a, b] {
+            ctx.process(&mouse(MouseEventKind::Moved, col, row));
+            ui.process(&mut ctx).unwrap();
+            ui.render(&mut ctx).unwrap();
+        }
+        assert_eq!(cell(&ui, a).background, Color::Black);
+        assert_eq!(cell(&ui, b).background, Color::Black);
+        ctx.process(&mouse(MouseEventKind::Moved, a.0, a.1));
+        ui.process(&mut ctx).unwrap();
+        ui.render(&mut ctx).unwrap();
+        assert_eq!(cell(&ui, a).background, Color::Blue);
+
+        for kind in [
+            MouseEventKind::Down(MouseButton::Left),
+            MouseEventKind::Up(MouseButton::Left),
+        ] {
+            ctx.process(&mouse(kind, b.0, b.1));
+            ui.process(&mut ctx).unwrap();
+        }
+        assert!(!clicked.get());
+    }
+
+    #[test]
+    fn stylesheet_restyles_by_id_class_and_state() {
+        let mut ui = UI::new(TestBackend::new(14, 9), Block::new().class("panel"));
+        ui.root.push(Widget::new("a").id("a"));
+        ui.root.push(Widget::new("b"));
+        let mut ctx = Context::with_size((14, 9));
+        ctx.stylesheet = Stylesheet::parse(
+            "
+            .panel { border: ascii; }
+            widget { margin: 0; padding: 1 0; }
+            #a:hover { background: blue; padding: 0; }
+            ",
+        )
+        .unwrap();
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
++------------+
+|            |
+| $$         |
+| ab         |
+| $$         |
+|            |
+|            |
+|            |
++------------+",
+        );
+
+        ctx.process(&mouse(MouseEventKind::Moved, 2, 3));
+        ui.process(&mut ctx).unwrap();
+        assert!(ctx.needs_redraw());
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
++------------+
+|            |
+| a$         |
+|  b         |
+|  $         |
+|            |
+|            |
+|            |
++------------+",
+        );
+        assert_eq!(ui.backend.cells()[2][2].background, Color::Blue);
+
+        // Moving off puts the padding back.
+        ctx.process(&mouse(MouseEventKind::Moved, 10, 6));
+        ui.process(&mut ctx).unwrap();
+        ui.render(&mut ctx).unwrap();
+        assert_eq!(ui.backend.cells()[3][2].symbol, "a");
+    }
+
+    #[test]
+    fn flush_downsamples_colors_the_terminal_lacks() {
+        let mut ui = UI::new(TestBackend::new(14, 9), Block::new());
+        ui.root
+            .push(Widget::new("a").style(Style::new().fg(Color::Rgb {
+                r: 250,
+                g: 10,
+                b: 10,
+            })));
+        ui.root.push(Widget::new("b"));
+        let mut ctx = Context::with_size((14, 9));
+        ctx.color_support = ColorSupport::Ansi16;
+        ui.render(&mut ctx).unwrap();
+        let text = ui.backend.cells()[4][4].clone();
+        assert_eq!(text.symbol, "a");
+        assert_eq!(text.color, Color::Red);
+        assert_eq!(text.background, Color::White);
+
+        // Without colors, the widget's background turns into reverse video.
+        // The focused widget is reversed already and stays so, and bold.
+        ctx.process(&key(KeyCode::Tab));
+        ui.process(&mut ctx).unwrap();
+        ctx.color_support = ColorSupport::Monochrome;
+        ctx.force_redraw = true;
+        ui.render(&mut ctx).unwrap();
+        let text = ui.backend.cells()[4][4].clone();
+        assert_eq!((text.color, text.background), (Color::Reset, Color::Reset));
+        assert!(text.attributes.has(Attribute::Reverse));
+        assert!(text.attributes.has(Attribute::Bold));
+        let unfocused = ui.backend.cells()[4][8].clone();
+        assert_eq!(unfocused.symbol, "b");
+        assert!(unfocused.attributes.has(Attribute::Reverse));
+        assert!(!unfocused.attributes.has(Attribute::Bold));
+        let border = ui.backend.cells()[0][0].clone();
+        assert_eq!(border.color, Color::Reset);
+        assert!(!border.attributes.has(Attribute::Reverse));
+    }
+
+    #[test]
+    fn render_reports_errors_instead_of_panicking() {
+        struct Closed;
+
+        impl Write for Closed {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let mut ctx = Context::with_size((10, 5));
+        let mut ui = UI::new(CrosstermBackend::new(Closed), Block::new());
+        assert!(matches!(ui.render(&mut ctx), Err(Error::Io(_))));
+
+        let mut ui = UI::new(TestBackend::new(10, 5), Block::new());
+        ui.root
+            .push(Widget::new("a").constraint(Constraint::Percentage(150)));
+        assert!(matches!(ui.render(&mut ctx), Err(Error::InvalidConfig(_))));
+        ui.root.remove(0);
+        ui.root.push(Widget::new("a").margin(|m| m.symbol('界')));
+        assert!(matches!(ui.process(&mut ctx), Err(Error::InvalidConfig(_))));
+        ui.root.remove(0);
+        ui.root
+            .push(Widget::new("a").margin(|m| m.set(AreaShort::Uniform(40000))));
+        assert!(matches!(ui.render(&mut ctx), Err(Error::LayoutOverflow)));
+    }
+
+    #[test]
+    fn inline_mode_renders_below_the_cursor_and_stays_in_scrollback() {
+        let mut backend = TestBackend::new(8, 6);
+        backend.print("$ run\r\n").unwrap();
+        let mut ctx = Context::with_size((8, 6));
+        let mut ui = UI::new(backend, Block::new()).screen(ScreenMode::Inline(4));
+        ui.enter(&mut ctx).unwrap();
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+$ run
+########
+#      #
+#      #
+########",
+        );
+        ui.exit(&mut ctx).unwrap();
+        // The frame stays put and the shell continues on the next line.
+        ui.backend.assert_frame(
+            "
+$ run
+########
+#      #
+#      #
+########",
+        );
+        assert_eq!(ui.backend.cursor_position().unwrap(), (0, 5));
+    }
+
+    #[test]
+    fn fullscreen_restores_the_main_screen() {
+        let mut backend = TestBackend::new(6, 4);
+        backend.print("shell").unwrap();
+        let mut ctx = Context::with_size((6, 4));
+        let mut ui = UI::new(backend, Block::new());
+        ui.enter(&mut ctx).unwrap();
+        ui.render(&mut ctx).unwrap();
+        assert!(ui.backend.is_alternate_screen());
+        ui.exit(&mut ctx).unwrap();
+        assert!(!ui.backend.is_alternate_screen());
+        ui.backend.assert_frame("shell");
+    }
+
+    #[test]
+    fn fixed_mode_only_touches_its_rect() {
+        let mut backend = TestBackend::new(10, 6);
+        backend.print("keep me").unwrap();
+        let mut ctx = Context::with_size((10, 6));
+        let rect = Rect::new((2, 1), (5, 4));
+        let mut ui = UI::new(backend, Block::new())
+            .screen(ScreenMode::Fixed(rect))
+            .keep_final_frame(false);
+        ui.enter(&mut ctx).unwrap();
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+keep me
+  #####
+  #   #
+  #   #
+  #####",
+        );
+        ui.exit(&mut ctx).unwrap();
+        ui.backend.assert_frame("keep me");
+    }
+
+    #[test]
+    fn widget_text_wraps_aligns_and_ellipsizes() {
+        let bare = |w: Widget<'static>| {
+            w.margin(|m| m.set(AreaShort::Uniform(0)))
+                .padding(|p| p.set(AreaShort::Uniform(0)))
+        };
+        let mut ui = UI::new(
+            TestBackend::new(12, 10),
+            Block::new().direction(Direction::Vertical),
+        );
+        ui.root.push(bare(
+            Widget::new("the quick brown fox")
+                .constraint(Constraint::Fixed(2))
+                .wrap(Wrap::Word)
+                .align(Align::Center)
+                .overflow(Overflow::Ellipsis),
+        ));
+        ui.root.push(bare(
+            Widget::new("hi\nyou")
+                .constraint(Constraint::Fixed(4))
+                .align(Align::Right)
+                .vertical_align(VerticalAlign::Bottom),
+        ));
+        let mut ctx = Context::with_size((12, 10));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+############
+#          #
+#   the    #
+#  quick…  #
+#          #
+#          #
+#  hi      #
+# you      #
+#          #
+############",
+        );
+    }
+
+    #[test]
+    fn wrapped_widgets_grow_to_fit_their_lines() {
+        let bare = |w: Widget<'static>| {
+            w.margin(|m| m.set(AreaShort::Uniform(0)))
+                .padding(|p| p.set(AreaShort::Uniform(0)))
+        };
+        let mut ui = UI::new(
+            TestBackend::new(14, 8),
+            Block::new().direction(Direction::Vertical),
+        );
+        ui.root.push(bare(
+            Widget::new("the quick brown fox jumps").wrap(Wrap::Word),
+        ));
+        ui.root.push(bare(Widget::new("end")));
+        let mut ctx = Context::with_size((14, 8));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+##############
+#            #
+# the quick  #
+# brown fox  #
+# jumps      #
+# end        #
+#            #
+##############",
+        );
+    }
+
+    #[test]
+    fn widget_spans_keep_their_own_style() {
+        let mut ui = UI::new(TestBackend::new(12, 5), Block::new());
+        ui.root.push(
+            Widget::new(Text::markup("[b]q[/] [blue on yellow]quit[/]").unwrap())
+                .margin(|m| m.set(AreaShort::Uniform(0)))
+                .padding(|p| p.set(AreaShort::Uniform(0))),
+        );
+        let mut ctx = Context::with_size((12, 5));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+############
+#          #
+# q quit   #
+#          #
+############",
+        );
+        let cells = ui.backend.cells();
+        assert!(cells[2][2].attributes.has(Attribute::Bold));
+        assert_eq!(cells[2][2].color, Color::Black);
+        assert!(!cells[2][3].attributes.has(Attribute::Bold));
+        assert_eq!(cells[2][4].color, Color::Blue);
+        assert_eq!(cells[2][4].background, Color::Yellow);
+        assert_eq!(cells[2][3].background, Color::White);
+    }
+
+    #[test]
+    fn borders_draw_styles_titles_and_junctions() {
+        let merged = || {
+            Block::new()
+                .constraint(Constraint::Fill(1))
+                .border(|b| b.style(BorderStyle::Plain).merge(true))
+        };
+        let mut root = Block::new().border(|b| {
+            b.style(BorderStyle::Rounded)
+                .color(Color::Blue)
+                .title(Title::new("ab").align(Align::Right))
+                .title(
+                    Title::new("x")
+                        .position(TitlePosition::Bottom)
+                        .align(Align::Center),
+                )
+        });
+        root.push(merged());
+        root.push(merged());
+        root.push(Block::new().border(|b| {
+            b.style(BorderStyle::Double).sides(Sides {
+                left: false,
+                ..Sides::ALL
+            })
+        }));
+        let mut ui = UI::new(TestBackend::new(16, 7), root);
+        let mut ctx = Context::with_size((16, 7));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+╭────────────ab╮
+│              │
+│ ┌───┬───┐══╗ │
+│ │   │   │  ║ │
+│ └───┴───┘══╝ │
+│              │
+╰──────x───────╯",
+        );
+        assert_eq!(ui.backend.cells()[0][0].color, Color::Blue);
+    }
+
+    #[test]
+    fn merged_borders_keep_clear_of_widget_margins() {
+        let mut ui = UI::new(TestBackend::new(16, 7), Block::new());
+        ui.root.push(
+            Block::new()
+                .constraint(Constraint::Fill(1))
+                .border(|b| b.style(BorderStyle::Plain).merge(true)),
+        );
+        ui.root.push(
+            Widget::new("w")
+                .margin(|m| m.set(AreaShort::Uniform(1)))
+                .padding(|p| p.set(AreaShort::Uniform(0))),
+        );
+        let mut ctx = Context::with_size((16, 7));
+        ui.render(&mut ctx).unwrap();
+        ui.backend.assert_frame(
+            "
+################
+#              #
+# ┌───────┐### #
+# │       │#w# #
+# └───────┘### #
+#              #
+################",
+        );
+    }
+
+    #[test]
+    fn distribute_splits_fill_by_weight() {
+        let items = [
+            (Constraint::Fixed(10), 3),
+            (Constraint::Percentage(50), 3),
+            (Constraint::Fill(1), 3),
+            (Constraint::Fill(2), 3),
+            (Constraint::Max(2), 3),
+        ];
+        assert_eq!(distribute(40, &items), vec![10, 20, 2, 6, 2]);
+    }
+
+    #[test]
+    fn frame_diff_marks_mismatched_lines() {
+        let mut backend = TestBackend::new(3, 2);
+        backend.print("ab").unwrap();
+        let diff = backend.frame_diff("ax").unwrap();
+        assert!(diff.contains("-   0 |ax|"));
+        assert!(diff.contains("+   0 |ab|"));
     }
 }